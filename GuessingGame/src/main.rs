@@ -1,30 +1,723 @@
-use rand::Rng;
+use clap::Parser;
+use colored::Colorize;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::io;
-fn main() {
-    println!("Welcome to the GUESSING GAME!!!");
-    let magic_number: u8;
-    magic_number = rand::thread_rng().gen_range(1..=100);
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Parser)]
+#[command(about = "Guess the magic number")]
+struct Args {
+    /// Smallest number the magic number can be
+    #[arg(long, env = "GAME_MIN", default_value_t = RANGE_MIN, allow_hyphen_values = true)]
+    min: i32,
+
+    /// Largest number the magic number can be
+    #[arg(long, env = "GAME_MAX", default_value_t = RANGE_MAX, allow_hyphen_values = true)]
+    max: i32,
+
+    /// Print feedback without colors
+    #[arg(long)]
+    no_color: bool,
+
+    /// JSON file where the scores of every win are kept, `scores.json` in the current directory
+    /// by default
+    #[arg(long, alias = "scores", default_value = "scores.json")]
+    leaderboard: PathBuf,
+
+    /// End the game when the number was not found within this many seconds
+    #[arg(long, value_name = "SECS")]
+    time_limit: Option<u64>,
+
+    /// Lose the game after this many wrong guesses
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_attempts: Option<u32>,
+
+    /// Keep playing, widening the range after good games and narrowing it after bad ones
+    #[arg(long)]
+    adaptive: bool,
+
+    /// Smallest range the adaptive mode narrows down to
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    min_width: u32,
+
+    /// Largest range the adaptive mode widens up to; not below --min-width
+    #[arg(long, default_value_t = 10_000, value_parser = clap::value_parser!(u32).range(1..))]
+    max_width: u32,
+
+    /// JSON file keeping the adaptive range between sessions
+    #[arg(long)]
+    skill_file: Option<PathBuf>,
+
+    /// Pick the magic number from this seed instead of a random one
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Save the seed, range and guesses of each game to this JSON file, for --replay
+    #[arg(long, value_name = "PATH")]
+    record: Option<PathBuf>,
+
+    /// Guess the letters of a secret word instead of a number
+    #[arg(long, conflicts_with_all = ["adaptive", "record", "replay"])]
+    word: bool,
+
+    /// Re-run a game saved with --record and print each guess with its outcome
+    #[arg(long, value_name = "PATH", conflicts_with = "record")]
+    replay: Option<PathBuf>,
+}
+
+/// How long the player may still think before the game ends.
+enum TimeLeft {
+    Unlimited,
+    Left(Duration),
+    OutOfTime,
+}
+
+fn time_left(time_limit: Option<Duration>, elapsed: Duration) -> TimeLeft {
+    match time_limit {
+        None => TimeLeft::Unlimited,
+        Some(limit) if elapsed >= limit => TimeLeft::OutOfTime,
+        Some(limit) => TimeLeft::Left(limit - elapsed),
+    }
+}
+
+/// Reads stdin on a separate thread so the game can stop waiting for a line when time runs out.
+fn spawn_line_reader() -> Receiver<io::Result<String>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lines() {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// What waiting for a line of input gave.
+enum Input {
+    Line(String),
+    OutOfTime,
+    /// Stdin was closed or could not be read; no more lines will come.
+    Closed,
+}
+
+/// Waits for the next line of input until the time is up.
+fn next_line(lines: &Receiver<io::Result<String>>, time_left: TimeLeft) -> Input {
+    let line = match time_left {
+        TimeLeft::Unlimited => lines.recv().ok(),
+        TimeLeft::Left(remaining) => match lines.recv_timeout(remaining) {
+            Ok(line) => Some(line),
+            Err(RecvTimeoutError::Timeout) => return Input::OutOfTime,
+            Err(RecvTimeoutError::Disconnected) => None,
+        },
+        TimeLeft::OutOfTime => return Input::OutOfTime,
+    };
+    match line {
+        Some(Ok(line)) => Input::Line(line),
+        // The reader thread ends at end of input and after a read error
+        Some(Err(_)) | None => Input::Closed,
+    }
+}
+
+/// Default inclusive bounds of the magic number; both may be negative.
+const RANGE_MIN: i32 = 1;
+const RANGE_MAX: i32 = 100;
+
+/// Printed when stdin ends in the middle of a game.
+const NO_INPUT: &str = "No more input, goodbye.";
+
+/// How many entries of the leaderboard are shown after a win.
+const TOP_N: usize = 5;
+
+#[derive(Serialize, Deserialize)]
+struct ScoreEntry {
+    name: String,
+    guesses: u32,
+    /// Seconds since the Unix epoch when the game was won.
+    timestamp: u64,
+}
+
+/// Reads the leaderboard, treating a missing file as an empty one.
+fn load_leaderboard(path: &Path) -> io::Result<Vec<ScoreEntry>> {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).map_err(io::Error::from),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it into place,
+/// so an interrupted write never leaves a truncated file behind.
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)
+}
+
+fn save_leaderboard(path: &Path, entries: &[ScoreEntry]) -> io::Result<()> {
+    write_atomically(path, &serde_json::to_string_pretty(entries)?)
+}
+
+/// Appends `entry` to the leaderboard stored at `path`.
+fn record_score(path: &Path, entry: ScoreEntry) -> io::Result<Vec<ScoreEntry>> {
+    let mut entries = load_leaderboard(path)?;
+    entries.push(entry);
+    save_leaderboard(path, &entries)?;
+    Ok(entries)
+}
+
+/// The `n` best entries, fewest guesses first; ties keep the earlier win first.
+fn top_scores(entries: &[ScoreEntry], n: usize) -> Vec<&ScoreEntry> {
+    let mut best: Vec<&ScoreEntry> = entries.iter().collect();
+    best.sort_by_key(|entry| (entry.guesses, entry.timestamp));
+    best.truncate(n);
+    best
+}
+
+/// Narrowest and widest ranges the adaptive mode may pick; `1 <= min <= max`.
+struct WidthBounds {
+    min: u32,
+    max: u32,
+}
+
+impl WidthBounds {
+    fn clamp(&self, width: u32) -> u32 {
+        width.clamp(self.min, self.max)
+    }
+}
+
+/// The skill estimate kept between sessions of the adaptive mode.
+#[derive(Serialize, Deserialize)]
+struct Skill {
+    /// How many numbers the range of the next game holds.
+    width: u32,
+}
+
+fn range_width(range: &RangeInclusive<i32>) -> u32 {
+    // The whole i32 range holds one number more than u32 can count
+    u32::try_from(i64::from(*range.end()) - i64::from(*range.start()) + 1).unwrap_or(u32::MAX)
+}
+
+fn range_with_width(start: i32, width: u32) -> RangeInclusive<i32> {
+    let end = (i64::from(start) + i64::from(width) - 1).min(i64::from(i32::MAX));
+    start..=end as i32
+}
+
+/// How many guesses halving the range each time needs in the worst case.
+fn optimal_guesses(width: u32) -> u32 {
+    u32::BITS - width.leading_zeros()
+}
+
+/// The range of the next adaptive game: twice as wide when the last game took no more
+/// guesses than halving the range would, half as wide when it took more than double that.
+fn next_range(previous: &RangeInclusive<i32>, last_guesses: u32, bounds: &WidthBounds) -> RangeInclusive<i32> {
+    let width = range_width(previous);
+    let optimal = optimal_guesses(width);
+    let next_width = if last_guesses <= optimal {
+        width.saturating_mul(2)
+    } else if last_guesses > 2 * optimal {
+        width / 2
+    } else {
+        width
+    };
+    range_with_width(*previous.start(), bounds.clamp(next_width))
+}
+
+fn load_skill(path: &Path) -> io::Result<Option<Skill>> {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).map(Some).map_err(io::Error::from),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn save_skill(path: &Path, skill: &Skill) -> io::Result<()> {
+    write_atomically(path, &serde_json::to_string_pretty(skill)?)
+}
+
+/// Everything needed to play a game again: the seed picks the magic number from the range.
+#[derive(Serialize, Deserialize)]
+struct GameRecord {
+    seed: u64,
+    range: RangeInclusive<i32>,
+    guesses: Vec<i32>,
+}
+
+/// The magic number of the game played with `seed`; the same seed and range always give the
+/// same number, as long as the rand version stays the same.
+fn magic_number(seed: u64, range: &RangeInclusive<i32>) -> i32 {
+    StdRng::seed_from_u64(seed).gen_range(range.clone())
+}
+
+fn save_record(path: &Path, record: &GameRecord) -> io::Result<()> {
+    write_atomically(path, &serde_json::to_string_pretty(record)?)
+}
+
+fn load_record(path: &Path) -> io::Result<GameRecord> {
+    serde_json::from_str(&fs::read_to_string(path)?).map_err(io::Error::from)
+}
+
+/// Compares every recorded guess with the magic number the record's seed picks.
+fn replay(record: &GameRecord) -> Vec<(i32, Ordering)> {
+    let magic_number = magic_number(record.seed, &record.range);
+    record.guesses.iter().map(|&guess| (guess, guess.cmp(&magic_number))).collect()
+}
+
+/// What a game made of one line of input.
+enum Turn {
+    /// The input was not a valid guess; it is not counted.
+    Retry,
+    /// A counted guess that did not end the game.
+    Continue,
+    /// A counted guess that won the game.
+    Solved,
+    /// A counted guess that lost the game.
+    Failed,
+}
+
+/// How a game ended.
+enum Outcome {
+    /// Solved with this many counted guesses.
+    Won(u32),
+    /// Too many wrong guesses.
+    Lost,
+    OutOfTime,
+    /// The input ended before the game did.
+    NoInput,
+}
+
+/// Prompts for guesses and hands each line to `turn` until the game is solved, failed, or out
+/// of time. `prompt` is given the number of guesses counted so far.
+fn guess_loop(
+    prompt: impl Fn(u32) -> String,
+    lines: &Receiver<io::Result<String>>,
+    time_limit: Option<Duration>,
+    mut turn: impl FnMut(&str) -> Turn,
+) -> Outcome {
+    let mut guesses: u32 = 0;
+    let start = Instant::now();
     loop {
-        println!("Enter a number");
-        let mut user_guess = String::new();
-        io::stdin()
-            .read_line(&mut user_guess)
-            .expect("Failed to read line");
-        let user_guess: u8 = match user_guess.trim().parse(){
+        println!("{}", prompt(guesses));
+        let line = match next_line(lines, time_left(time_limit, start.elapsed())) {
+            Input::Line(line) => line,
+            Input::OutOfTime => return Outcome::OutOfTime,
+            Input::Closed => return Outcome::NoInput,
+        };
+        match turn(&line) {
+            Turn::Retry => continue,
+            Turn::Continue => guesses += 1,
+            Turn::Solved => return Outcome::Won(guesses + 1),
+            Turn::Failed => return Outcome::Lost,
+        }
+    }
+}
+
+/// Plays one game, which is lost after `max_attempts` wrong guesses when that is set.
+fn play_round(
+    range: &RangeInclusive<i32>,
+    magic_number: i32,
+    lines: &Receiver<io::Result<String>>,
+    time_limit: Option<Duration>,
+    max_attempts: Option<u32>,
+    guesses_made: &mut Vec<i32>,
+) -> Outcome {
+    let (min, max) = (*range.start(), *range.end());
+    let prompt = |guesses: u32| match max_attempts {
+        Some(attempts) => format!("Enter a number between {min} and {max} ({} attempts left)", attempts - guesses),
+        None => format!("Enter a number between {min} and {max}"),
+    };
+    // How far the last counted guess was from the magic number
+    let mut previous_distance: Option<i64> = None;
+    guess_loop(prompt, lines, time_limit, |input| {
+        let user_guess: i32 = match input.trim().parse(){
             Ok(num) => num,
-            Err(_) => continue,
+            Err(_) => return Turn::Retry,
         };
+        if !range.contains(&user_guess) {
+            println!("{user_guess} is outside of {min}..={max}");
+            return Turn::Retry;
+        }
+        guesses_made.push(user_guess);
         println!("Your guesed number was:");
 
         match user_guess.cmp(&magic_number) {
-            Ordering::Less => println!("Too small!"),
-            Ordering::Greater => println!("Too big!"),
+            Ordering::Less => println!("{}", "Too small!".blue()),
+            Ordering::Greater => println!("{}", "Too big!".red()),
             Ordering::Equal => {
-                println!("Perfect! You have win!");
-                break;
+                println!("{}", "Perfect! You have win!".green());
+                return Turn::Solved;
             }
         }
+        let distance = (i64::from(user_guess) - i64::from(magic_number)).abs();
+        if let Some(previous) = previous_distance {
+            match distance.cmp(&previous) {
+                Ordering::Less => println!("{}", "Warmer!".yellow()),
+                Ordering::Greater => println!("{}", "Colder!".cyan()),
+                Ordering::Equal => println!("Just as far as last time"),
+            }
+        }
+        previous_distance = Some(distance);
+        match max_attempts {
+            Some(attempts) if guesses_made.len() >= attempts as usize => Turn::Failed,
+            _ => Turn::Continue,
+        }
+    })
+}
+
+/// The secret words of the word mode, all lowercase ASCII letters.
+const WORDS: [&str; 16] = [
+    "rust", "ferris", "cargo", "borrow", "lifetime", "trait", "closure", "iterator",
+    "pattern", "crate", "module", "vector", "string", "thread", "channel", "macro",
+];
+
+/// How many wrong letters the word mode allows before the game is lost.
+const MAX_MISSES: u32 = 7;
+
+/// The word picked by `seed`, the same way [`magic_number`] picks a number.
+fn secret_word(seed: u64) -> &'static str {
+    WORDS[StdRng::seed_from_u64(seed).gen_range(0..WORDS.len())]
+}
+
+/// The word with every letter not guessed yet shown as `_`, letters separated by spaces.
+fn reveal(word: &str, guessed: &HashSet<char>) -> String {
+    word.chars()
+        .map(|c| if guessed.contains(&c) { c.to_string() } else { "_".to_string() })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Plays one word game, which is lost after [`MAX_MISSES`] wrong letters.
+fn play_word(word: &str, lines: &Receiver<io::Result<String>>, time_limit: Option<Duration>) -> Outcome {
+    let mut guessed = HashSet::new();
+    let mut misses: u32 = 0;
+    println!("{}", reveal(word, &guessed));
+    guess_loop(|_| "Guess a letter".to_string(), lines, time_limit, |input| {
+        let mut chars = input.trim().chars();
+        let letter = match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_alphabetic() => c.to_ascii_lowercase(),
+            _ => return Turn::Retry,
+        };
+        if !guessed.insert(letter) {
+            println!("You already tried {letter}");
+            return Turn::Retry;
+        }
+        if word.contains(letter) {
+            println!("{}", format!("There is a {letter}!").green());
+        } else {
+            misses += 1;
+            println!("{}", format!("No {letter}, {} misses left", MAX_MISSES - misses).red());
+        }
+        let shown = reveal(word, &guessed);
+        println!("{shown}");
+        if !shown.contains('_') {
+            println!("{}", "Perfect! You have win!".green());
+            Turn::Solved
+        } else if misses >= MAX_MISSES {
+            Turn::Failed
+        } else {
+            Turn::Continue
+        }
+    })
+}
+
+fn print_leaderboard(path: &Path, entry: ScoreEntry) {
+    let (guesses, timestamp) = (entry.guesses, entry.timestamp);
+    match record_score(path, entry) {
+        Ok(entries) => {
+            if let Some(best) = top_scores(&entries, 1).first() {
+                if best.guesses == guesses && best.timestamp == timestamp {
+                    println!("{}", format!("New all-time best: {guesses} guesses!").green());
+                } else {
+                    println!("All-time best: {} with {} guesses", best.name, best.guesses);
+                }
+            }
+            println!("Top {TOP_N}:");
+            for (rank, entry) in top_scores(&entries, TOP_N).iter().enumerate() {
+                println!("{}. {} - {} guesses", rank + 1, entry.name, entry.guesses);
+            }
+        }
+        Err(e) => eprintln!("Could not update {}: {e}", path.display()),
+    }
+}
+
+/// Colors are used only when they were not disabled and stdout is a terminal,
+/// so piped output keeps the plain messages.
+fn should_colorize(no_color: bool, stdout_is_tty: bool) -> bool {
+    !no_color && stdout_is_tty
+}
+
+fn main() {
+    let args = Args::parse();
+    colored::control::set_override(should_colorize(args.no_color, io::stdout().is_terminal()));
+
+    if let Some(path) = &args.replay {
+        let record = match load_record(path) {
+            Ok(record) => record,
+            Err(e) => {
+                eprintln!("Could not read {}: {e}", path.display());
+                return;
+            }
+        };
+        println!("Replaying seed {} in {}..={}", record.seed, record.range.start(), record.range.end());
+        for (step, (guess, ordering)) in replay(&record).iter().enumerate() {
+            println!("{}. {guess} -> {ordering:?}", step + 1);
+        }
+        return;
+    }
+
+    if args.min >= args.max {
+        eprintln!("The smallest number ({}) must be below the largest one ({})", args.min, args.max);
+        std::process::exit(2);
+    }
+    if args.min_width > args.max_width {
+        eprintln!("--min-width ({}) must not be above --max-width ({})", args.min_width, args.max_width);
+        std::process::exit(2);
+    }
+
+    println!("Welcome to the GUESSING GAME!!!");
+    if !args.word {
+        println!("The magic number is between {} and {}", args.min, args.max);
+    }
+    println!("What is your name?");
+    let mut name = String::new();
+    if !matches!(io::stdin().read_line(&mut name), Ok(bytes) if bytes > 0) {
+        println!("{NO_INPUT}");
+        return;
+    }
+    let name = name.trim().to_string();
+
+    let lines = spawn_line_reader();
+    let time_limit = args.time_limit.map(Duration::from_secs);
+    let bounds = WidthBounds { min: args.min_width, max: args.max_width };
+
+    let mut range = args.min..=args.max;
+    if let (true, Some(path)) = (args.adaptive, &args.skill_file) {
+        match load_skill(path) {
+            Ok(Some(skill)) => range = range_with_width(args.min, bounds.clamp(skill.width)),
+            Ok(None) => {}
+            Err(e) => eprintln!("Could not read {}: {e}", path.display()),
+        }
+    }
+
+    let mut games: u32 = 0;
+    let mut total_guesses: u32 = 0;
+    let mut seed = args.seed.unwrap_or_else(rand::random);
+    if args.word {
+        let word = secret_word(seed);
+        match play_word(word, &lines, time_limit) {
+            Outcome::Won(guesses) => println!("You found {word} with {guesses} letters"),
+            Outcome::Lost | Outcome::OutOfTime => println!("The word was {word}"),
+            Outcome::NoInput => println!("{NO_INPUT}"),
+        }
+        return;
+    }
+    loop {
+        let magic_number = magic_number(seed, &range);
+        let mut guesses_made = Vec::new();
+        let outcome = play_round(&range, magic_number, &lines, time_limit, args.max_attempts, &mut guesses_made);
+        if let Some(path) = &args.record {
+            let record = GameRecord { seed, range: range.clone(), guesses: guesses_made };
+            if let Err(e) = save_record(path, &record) {
+                eprintln!("Could not update {}: {e}", path.display());
+            }
+        }
+        // Each game of a session gets its own seed, so any of them can be replayed alone
+        seed = seed.wrapping_add(1);
+        let guesses = match outcome {
+            Outcome::Won(guesses) => guesses,
+            Outcome::Lost => {
+                println!("You lose! The number was {magic_number}");
+                return;
+            }
+            Outcome::OutOfTime => {
+                println!("Out of time! The number was {magic_number}");
+                return;
+            }
+            Outcome::NoInput => {
+                println!("{NO_INPUT}");
+                return;
+            }
+        };
+        println!("Magic number was:{magic_number}");
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        print_leaderboard(&args.leaderboard, ScoreEntry { name: name.clone(), guesses, timestamp });
+
+        if !args.adaptive {
+            return;
+        }
+        games += 1;
+        total_guesses += guesses;
+        println!("Average guesses this session: {:.1}", f64::from(total_guesses) / f64::from(games));
+        range = next_range(&range, guesses, &bounds);
+        if let Some(path) = &args.skill_file {
+            if let Err(e) = save_skill(path, &Skill { width: range_width(&range) }) {
+                eprintln!("Could not update {}: {e}", path.display());
+            }
+        }
+
+        println!("Play again? (y/n)");
+        match next_line(&lines, TimeLeft::Unlimited) {
+            Input::Line(answer) if answer.trim().eq_ignore_ascii_case("y") => {}
+            _ => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn width_bounds_keep_stored_widths_in_range() {
+        let bounds = WidthBounds { min: 10, max: 100 };
+        assert_eq!(bounds.clamp(0), 10);
+        assert_eq!(bounds.clamp(50), 50);
+        assert_eq!(bounds.clamp(u32::MAX), 100);
+        assert_eq!(range_with_width(1, bounds.clamp(0)), 1..=10);
+    }
+
+    #[test]
+    fn width_options_must_be_positive() {
+        assert!(Args::try_parse_from(["guessing_game", "--min-width", "0"]).is_err());
+        assert!(Args::try_parse_from(["guessing_game", "--max-width", "0"]).is_err());
+        assert!(Args::try_parse_from(["guessing_game", "--min-width", "5", "--max-width", "5"]).is_ok());
+    }
+
+    fn entry(name: &str, guesses: u32, timestamp: u64) -> ScoreEntry {
+        ScoreEntry { name: name.to_string(), guesses, timestamp }
+    }
+
+    #[test]
+    fn colors_only_on_a_terminal_without_no_color() {
+        assert!(should_colorize(false, true));
+        assert!(!should_colorize(true, true));
+        assert!(!should_colorize(false, false));
+        assert!(!should_colorize(true, false));
+    }
+
+    #[test]
+    fn leaderboard_starts_empty_and_keeps_every_win() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scores.json");
+        assert!(load_leaderboard(&path).unwrap().is_empty());
+
+        record_score(&path, entry("ann", 7, 1)).unwrap();
+        record_score(&path, entry("bob", 4, 2)).unwrap();
+        let entries = record_score(&path, entry("cid", 9, 3)).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(load_leaderboard(&path).unwrap().len(), 3);
+        // The temporary file was renamed into place
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[test]
+    fn top_scores_fewest_guesses_first() {
+        let entries = vec![
+            entry("ann", 7, 1),
+            entry("bob", 4, 2),
+            entry("cid", 9, 3),
+            entry("dee", 4, 0),
+            entry("eve", 5, 4),
+            entry("fay", 6, 5),
+            entry("gus", 8, 6),
+        ];
+        let names: Vec<&str> = top_scores(&entries, TOP_N).iter().map(|entry| entry.name.as_str()).collect();
+        // Equal scores keep the earlier win first
+        assert_eq!(names, ["dee", "bob", "eve", "fay", "ann"]);
+        assert!(top_scores(&[], TOP_N).is_empty());
+    }
+
+    #[test]
+    fn negative_ranges_compare_like_positive_ones() {
+        let range = -1000..=1000;
+        let magic = magic_number(3, &range);
+        assert!(range.contains(&magic));
+        let record = GameRecord { seed: 3, range: range.clone(), guesses: vec![-1000, 1000, magic] };
+        let outcomes: Vec<Ordering> = replay(&record).into_iter().map(|(_, ordering)| ordering).collect();
+        let expected_low = if magic == -1000 { Ordering::Equal } else { Ordering::Less };
+        let expected_high = if magic == 1000 { Ordering::Equal } else { Ordering::Greater };
+        assert_eq!(outcomes, [expected_low, expected_high, Ordering::Equal]);
+
+        let below_zero = -50..=-10;
+        assert!(below_zero.contains(&magic_number(9, &below_zero)));
+        let args = Args::try_parse_from(["guessing_game", "--min", "-1000", "--max", "-1"]).unwrap();
+        assert_eq!((args.min, args.max), (-1000, -1));
+    }
+
+    #[test]
+    fn time_runs_out_at_the_limit() {
+        assert!(matches!(time_left(None, Duration::from_secs(1_000)), TimeLeft::Unlimited));
+        let limit = Some(Duration::from_secs(10));
+        assert!(matches!(time_left(limit, Duration::from_secs(4)), TimeLeft::Left(left) if left == Duration::from_secs(6)));
+        assert!(matches!(time_left(limit, Duration::from_secs(10)), TimeLeft::OutOfTime));
+        assert!(matches!(time_left(limit, Duration::from_secs(11)), TimeLeft::OutOfTime));
+    }
+
+    #[test]
+    fn waiting_for_input_stops_when_time_is_up_or_input_ends() {
+        let (sender, lines) = mpsc::channel();
+        assert!(matches!(next_line(&lines, TimeLeft::Left(Duration::from_millis(10))), Input::OutOfTime));
+        assert!(matches!(next_line(&lines, TimeLeft::OutOfTime), Input::OutOfTime));
+        sender.send(Ok("42".to_string())).unwrap();
+        assert!(matches!(next_line(&lines, TimeLeft::Unlimited), Input::Line(line) if line == "42"));
+        drop(sender);
+        assert!(matches!(next_line(&lines, TimeLeft::Unlimited), Input::Closed));
+    }
+
+    #[test]
+    fn next_range_follows_the_last_game() {
+        let bounds = WidthBounds { min: 10, max: 1_000 };
+        // Halving 1..=100 takes at most 7 guesses
+        assert_eq!(optimal_guesses(100), 7);
+        assert_eq!(next_range(&(1..=100), 5, &bounds), 1..=200);
+        assert_eq!(next_range(&(1..=100), 7, &bounds), 1..=200);
+        assert_eq!(next_range(&(1..=100), 10, &bounds), 1..=100);
+        assert_eq!(next_range(&(1..=100), 15, &bounds), 1..=50);
+        assert_eq!(next_range(&(1..=800), 1, &bounds), 1..=1_000);
+        assert_eq!(next_range(&(-5..=9), 30, &bounds), -5..=4);
+    }
+
+    #[test]
+    fn replaying_a_saved_game_gives_the_same_outcomes() {
+        let range = 1..=100;
+        let magic = magic_number(1234, &range);
+        let guesses = vec![1, 100, 50, magic];
+        let record = GameRecord { seed: 1234, range, guesses };
+        let played = replay(&record);
+        assert_eq!(played.last(), Some(&(magic, Ordering::Equal)));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("game.json");
+        save_record(&path, &record).unwrap();
+        assert_eq!(replay(&load_record(&path).unwrap()), played);
+    }
+
+    #[test]
+    fn reveal_shows_only_guessed_letters() {
+        let guessed: HashSet<char> = ['r', 't', 'x'].into_iter().collect();
+        assert_eq!(reveal("rust", &guessed), "r _ _ t");
+        assert_eq!(reveal("trait", &guessed), "t r _ _ t");
+        assert_eq!(reveal("rust", &HashSet::new()), "_ _ _ _");
+        let everything: HashSet<char> = "rust".chars().collect();
+        assert!(!reveal("rust", &everything).contains('_'));
+    }
+
+    #[test]
+    fn secret_words_come_from_the_list() {
+        assert_eq!(secret_word(5), secret_word(5));
+        assert!((0..100).all(|seed| WORDS.contains(&secret_word(seed))));
     }
-    println!("Magic number was:{magic_number}");
 }
@@ -0,0 +1,66 @@
+//! Runs the game binary on scripted stdin and checks it ends when the input does.
+
+use std::io::Write;
+use std::process::{Child, Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+/// Starts the game with `args`, writes `input` to its stdin and closes it.
+fn run(args: &[&str], input: &str) -> Child {
+    let dir = std::env::temp_dir();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_guessing_game"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start the game");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child
+}
+
+/// Waits up to ten seconds for the game to exit, killing it otherwise.
+fn finish(mut child: Child) -> Output {
+    let start = Instant::now();
+    while child.try_wait().unwrap().is_none() {
+        if start.elapsed() > Duration::from_secs(10) {
+            child.kill().unwrap();
+            panic!("the game kept running after its input ended");
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn ends_when_the_guesses_run_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let leaderboard = dir.path().join("scores.json");
+    let args = ["--seed", "1", "--min", "1", "--max", "1000", "--no-color", "--leaderboard", leaderboard.to_str().unwrap()];
+    // Seed 1 picks neither 1 nor 1000, so the game is still running when stdin ends
+    let output = finish(run(&args, "ann\n1\n1000\nnot a number\n"));
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.trim_end().ends_with("No more input, goodbye."), "{}", stdout);
+    assert!(!leaderboard.exists());
+}
+
+#[test]
+fn ends_before_asking_for_a_name() {
+    let output = finish(run(&["--no-color"], ""));
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("No more input, goodbye."));
+}
+
+#[test]
+fn a_scripted_win_reaches_the_leaderboard() {
+    let dir = tempfile::tempdir().unwrap();
+    let leaderboard = dir.path().join("scores.json");
+    let guesses: String = (1..=100).map(|guess| format!("{guess}\n")).collect();
+    let args = ["--no-color", "--leaderboard", leaderboard.to_str().unwrap()];
+    let output = finish(run(&args, &format!("ann\n{guesses}")));
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Perfect! You have win!"), "{}", stdout);
+    assert!(std::fs::read_to_string(&leaderboard).unwrap().contains("\"ann\""));
+}
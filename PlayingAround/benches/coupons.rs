@@ -0,0 +1,56 @@
+//! Benchmarks of the coupon generation hot path, reported in coupons per second.
+//!
+//! Run with `cargo bench --bench coupons`; criterion keeps the previous results under
+//! `target/criterion` and reports how much each case moved since.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use playing_around::coupons::{coupon_generator, Charset};
+
+/// `(len, number_coupons, charset)` cases: a sparse request, a large one, a small charset, and
+/// one above the dense threshold that takes the permutation path.
+fn cases() -> Vec<(u16, usize, &'static str)> {
+    vec![
+        (10, 10_000, "alphanumeric"),
+        (10, 200_000, "alphanumeric"),
+        (12, 100_000, "digits"),
+        (9, 60_000, "digits"),
+    ]
+}
+
+fn generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("coupon_generator");
+    group.sample_size(10);
+    for (len, number_coupons, charset_name) in cases() {
+        let charset: Charset = charset_name.parse().unwrap();
+        group.throughput(Throughput::Elements(number_coupons as u64));
+        let id = BenchmarkId::from_parameter(format!("len={len}/n={number_coupons}/{charset_name}"));
+        group.bench_with_input(id, &charset, |b, charset| {
+            b.iter(|| coupon_generator(len, number_coupons, "LISA", charset, None).unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn threads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("coupon_generator_threads");
+    group.sample_size(10);
+    let number_coupons = 100_000;
+    let charset = Charset::alphanumeric();
+    group.throughput(Throughput::Elements(number_coupons as u64));
+    let max_threads = std::thread::available_parallelism().map_or(1, usize::from);
+    let mut counts = vec![1, 2, 4, 8, max_threads];
+    counts.retain(|&count| count <= max_threads);
+    counts.dedup();
+    for count in counts {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(count).build().unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| {
+            b.iter(|| {
+                pool.install(|| coupon_generator(10, number_coupons, "LISA", &charset, None).unwrap())
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, generation, threads);
+criterion_main!(benches);
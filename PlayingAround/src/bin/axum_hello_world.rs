@@ -1,21 +1,696 @@
+use auction_schipol::{parse_items, write_csv};
 use axum::{
-    routing::get,
+    body::Body,
+    extract::{
+        multipart::{Multipart, MultipartError},
+        rejection::JsonRejection,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        FromRef, MatchedPath, Query, Request, State,
+    },
+    middleware::{self, Next},
+    routing::{get, post},
+    Json,
     Router,
-    http::StatusCode,
-    response::IntoResponse,
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
 };
+use clap::Parser;
+use playing_around::config::Config;
+use playing_around::coupon_stream::{coupon_stream, write_coupons_to_csv, DEFAULT_BUFFER_SIZE};
+use playing_around::coupons::{coupon_generator, Charset, CouponError};
+use playing_around::shapes::{shapes_to_svg, Shape, ShapeSpec};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::io::ReaderStream;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+/// What the running binary was built from.
+#[derive(Serialize)]
+struct VersionInfo {
+    version: &'static str,
+    git_commit: &'static str,
+    build_timestamp: &'static str,
+}
 
 async fn hello() -> impl IntoResponse {
     (StatusCode::OK, "HI LISA")
 }
 
+/// The body of `/health`.
+#[derive(Serialize)]
+struct Health {
+    status: &'static str,
+}
+
+/// Answers as soon as the server accepts requests, for load balancer health checks.
+async fn health() -> Json<Health> {
+    Json(Health { status: "ok" })
+}
+
+async fn version() -> Json<VersionInfo> {
+    Json(VersionInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("GIT_COMMIT"),
+        build_timestamp: env!("BUILD_TIMESTAMP"),
+    })
+}
+
+/// Prefix added to every text message echoed back over the WebSocket.
+const ECHO_PREFIX: &str = "echo: ";
+
+async fn ws(upgrade: WebSocketUpgrade) -> impl IntoResponse {
+    upgrade.on_upgrade(echo)
+}
+
+/// Echoes text messages with a prefix and binary frames unchanged, until the client says "bye".
+async fn echo(mut socket: WebSocket) {
+    while let Some(Ok(message)) = socket.recv().await {
+        let reply = match message {
+            Message::Text(text) if text == "bye" => {
+                let _ = socket.send(Message::Close(None)).await;
+                return;
+            }
+            Message::Text(text) => Message::Text(format!("{ECHO_PREFIX}{text}")),
+            Message::Binary(data) => Message::Binary(data),
+            Message::Close(_) => return,
+            // Pings are answered automatically
+            Message::Ping(_) | Message::Pong(_) => continue,
+        };
+        if socket.send(reply).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Response header listing the uploaded files that were not valid auction JSON.
+const SKIPPED_FILES_HEADER: &str = "x-skipped-files";
+
+/// Combines every uploaded JSON dump into one CSV, like the auction_schipol binary does
+/// for a directory. Files that don't parse are left out and named in [`SKIPPED_FILES_HEADER`].
+async fn combine_auction(mut multipart: Multipart) -> Result<Response, MultipartError> {
+    let mut items = Vec::new();
+    let mut skipped = Vec::new();
+    while let Some(field) = multipart.next_field().await? {
+        let name = field
+            .file_name()
+            .or(field.name())
+            .unwrap_or("unnamed")
+            .to_string();
+        let content = field.bytes().await?;
+        match parse_items(&content) {
+            Ok(parsed) => items.extend(parsed),
+            Err(e) => {
+                eprintln!("Skipping {}: {}", name, e);
+                skipped.push(name);
+            }
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let csv = match write_csv(&mut writer, &items) {
+        Ok(()) => writer.into_inner().map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    let csv = match csv {
+        Ok(csv) => csv,
+        Err(e) => return Ok((StatusCode::INTERNAL_SERVER_ERROR, e).into_response()),
+    };
+
+    let mut response = ([(header::CONTENT_TYPE, "text/csv")], csv).into_response();
+    if !skipped.is_empty() {
+        // File names that can't be sent as a header value are reported as a count instead
+        let value = HeaderValue::from_str(&skipped.join(", "))
+            .unwrap_or_else(|_| HeaderValue::from(skipped.len()));
+        response.headers_mut().insert(SKIPPED_FILES_HEADER, value);
+    }
+    Ok(response)
+}
+
+/// Renders the posted shapes, in the same JSON form as `/area` takes, as one SVG document, or
+/// answers 400 when the body is not a list of shapes or a dimension is not a positive number.
+async fn shapes_svg(shapes: Result<Json<Vec<ShapeSpec>>, JsonRejection>) -> Response {
+    let Json(shapes) = match shapes {
+        Ok(shapes) => shapes,
+        Err(rejection) => return (StatusCode::BAD_REQUEST, rejection.body_text()).into_response(),
+    };
+    match shapes.iter().try_for_each(ShapeSpec::validate) {
+        Ok(()) => ([(header::CONTENT_TYPE, "image/svg+xml")], shapes_to_svg(&shapes)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+/// The most coupons one request to `/coupons` may ask for.
+const MAX_COUPONS_PER_REQUEST: usize = 100_000;
+
+/// The most coupons one request to `/coupons.csv` may ask for; they are streamed, so only the
+/// set keeping them unique grows with the count.
+const MAX_CSV_COUPONS_PER_REQUEST: usize = 10_000_000;
+
+/// Bytes of CSV that may wait for the client of `/coupons.csv` before generation pauses.
+const CSV_PIPE_CAPACITY: usize = 64 * 1024;
+
+/// The query of `/coupons`; what is left out comes from [`Config::coupons`].
+#[derive(Deserialize)]
+struct CouponQuery {
+    length: Option<u16>,
+    count: Option<usize>,
+    initials: Option<String>,
+}
+
+impl CouponQuery {
+    /// The length, count and initials asked for, or an error when the count is above `max`.
+    fn resolve(self, config: &Config, max: usize) -> Result<(u16, usize, String), CouponApiError> {
+        let defaults = &config.coupons;
+        let count = self.count.unwrap_or(defaults.count);
+        if count > max {
+            return Err(CouponApiError::TooManyRequested(count, max));
+        }
+        Ok((
+            self.length.unwrap_or(defaults.len),
+            count,
+            self.initials.unwrap_or_else(|| defaults.initials.clone()),
+        ))
+    }
+}
+
+/// A coupon request that failed, answered 400 when the request asked for the impossible.
+enum CouponApiError {
+    TooManyRequested(usize, usize),
+    Generation(CouponError),
+}
+
+impl IntoResponse for CouponApiError {
+    fn into_response(self) -> Response {
+        match self {
+            CouponApiError::TooManyRequested(count, max) => (
+                StatusCode::BAD_REQUEST,
+                format!("Cannot generate {count} coupons in one request, the maximum is {max}"),
+            )
+                .into_response(),
+            CouponApiError::Generation(
+                e @ (CouponError::InitialsTooLong(..) | CouponError::TooManyCoupons(..)),
+            ) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+            CouponApiError::Generation(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        }
+    }
+}
+
+/// Generates unique coupons and returns them as a JSON array of strings.
+async fn coupons(
+    State(config): State<Arc<Config>>,
+    Query(query): Query<CouponQuery>,
+) -> Result<Json<Vec<String>>, CouponApiError> {
+    let (length, count, initials) = query.resolve(&config, MAX_COUPONS_PER_REQUEST)?;
+    // Generation keeps every core busy, which must not stall the async workers
+    let coupons = tokio::task::spawn_blocking(move || {
+        coupon_generator(length, count, &initials, &Charset::default(), None)
+    })
+    .await
+    .expect("coupon generation panicked")
+    .map_err(CouponApiError::Generation)?;
+    Ok(Json(coupons))
+}
+
+/// Streams generated coupons as a CSV download, written while the client reads it.
+async fn coupons_csv(
+    State(config): State<Arc<Config>>,
+    Query(query): Query<CouponQuery>,
+) -> Result<Response, CouponApiError> {
+    let (length, count, initials) = query.resolve(&config, MAX_CSV_COUPONS_PER_REQUEST)?;
+    let coupons = coupon_stream(length, count, &initials, Charset::default(), DEFAULT_BUFFER_SIZE)
+        .map_err(CouponApiError::Generation)?;
+
+    // The CSV is written into one end of a pipe while the body reads the other, so writing
+    // waits whenever the client falls behind
+    let (writer, reader) = tokio::io::duplex(CSV_PIPE_CAPACITY);
+    tokio::spawn(async move {
+        // The status line is sent by now, so a failure can only cut the download short
+        if let Err(e) = write_coupons_to_csv(writer, coupons).await {
+            eprintln!("Stopped streaming coupons.csv: {}", e);
+        }
+    });
+    let headers = [
+        (header::CONTENT_TYPE, "text/csv"),
+        (header::CONTENT_DISPOSITION, "attachment; filename=\"coupons.csv\""),
+    ];
+    Ok((headers, Body::from_stream(ReaderStream::new(reader))).into_response())
+}
+
+/// The body of a `/area` response.
+#[derive(Serialize)]
+struct AreaResponse {
+    area: f32,
+}
+
+/// Computes the area of one posted shape, e.g. `{"type": "circle", "radius": 2}`, or answers 422
+/// when the body is not such a shape or a dimension is not a positive number.
+async fn area(shape: Result<Json<ShapeSpec>, JsonRejection>) -> Response {
+    let Json(shape) = match shape {
+        Ok(shape) => shape,
+        Err(rejection @ (JsonRejection::JsonDataError(_) | JsonRejection::JsonSyntaxError(_))) => {
+            return (StatusCode::UNPROCESSABLE_ENTITY, rejection.body_text()).into_response()
+        }
+        Err(rejection) => return rejection.into_response(),
+    };
+    match shape.validate() {
+        Ok(()) => Json(AreaResponse { area: shape.area() }).into_response(),
+        Err(e) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response(),
+    }
+}
+
+/// Upper bounds, in seconds, of the request latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// The requests seen for one route.
+#[derive(Default)]
+struct RouteMetrics {
+    count: u64,
+    /// How many requests finished within each of [`LATENCY_BUCKETS`], cumulatively.
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    total_seconds: f64,
+}
+
+/// Request counts and latencies per route, kept in memory since the server started.
+#[derive(Default)]
+struct Metrics {
+    routes: Mutex<BTreeMap<String, RouteMetrics>>,
+}
+
+impl Metrics {
+    fn record(&self, route: &str, seconds: f64) {
+        let mut routes = self.routes.lock();
+        let metrics = routes.entry(route.to_string()).or_default();
+        metrics.count += 1;
+        metrics.total_seconds += seconds;
+        for (bucket, &bound) in metrics.buckets.iter_mut().zip(LATENCY_BUCKETS.iter()) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    fn render(&self) -> String {
+        let routes = self.routes.lock();
+        let mut text = String::new();
+        text.push_str("# HELP http_requests_total Number of HTTP requests handled, per route.\n");
+        text.push_str("# TYPE http_requests_total counter\n");
+        for (route, metrics) in routes.iter() {
+            let _ = writeln!(text, "http_requests_total{{route=\"{route}\"}} {}", metrics.count);
+        }
+        text.push_str("# HELP http_request_duration_seconds Time spent handling HTTP requests, per route.\n");
+        text.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for (route, metrics) in routes.iter() {
+            for (bound, bucket) in LATENCY_BUCKETS.iter().zip(metrics.buckets.iter()) {
+                let _ = writeln!(text, "http_request_duration_seconds_bucket{{route=\"{route}\",le=\"{bound}\"}} {bucket}");
+            }
+            let _ = writeln!(text, "http_request_duration_seconds_bucket{{route=\"{route}\",le=\"+Inf\"}} {}", metrics.count);
+            let _ = writeln!(text, "http_request_duration_seconds_sum{{route=\"{route}\"}} {}", metrics.total_seconds);
+            let _ = writeln!(text, "http_request_duration_seconds_count{{route=\"{route}\"}} {}", metrics.count);
+        }
+        text
+    }
+}
+
+/// Times every request and records it under the route pattern it matched, e.g. `/shapes/svg`.
+async fn track_metrics(State(metrics): State<Arc<Metrics>>, request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+    let start = Instant::now();
+    let response = next.run(request).await;
+    metrics.record(&route, start.elapsed().as_secs_f64());
+    response
+}
+
+async fn metrics(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics.render())
+}
+
+/// Command-line options of the server; they override the config file and the environment.
+#[derive(Parser)]
+#[command(about = "Run the axum example server")]
+struct Args {
+    /// Port to listen on, instead of `PORT` or the config file
+    #[arg(long)]
+    port: Option<u16>,
+}
+
+/// The file the server reads its [`Config`] from, when it exists.
+const CONFIG_FILE: &str = "config.toml";
+
+/// Counts the requests of the current one-second window, for [`Config::rate_limit`].
+struct RateLimiter {
+    window: Mutex<(Instant, u32)>,
+}
+
+impl RateLimiter {
+    fn new() -> Self {
+        RateLimiter { window: Mutex::new((Instant::now(), 0)) }
+    }
+
+    /// Whether one more request fits in the current window of at most `limit` requests.
+    fn try_acquire(&self, limit: u32) -> bool {
+        let mut window = self.window.lock();
+        if window.0.elapsed() >= Duration::from_secs(1) {
+            *window = (Instant::now(), 0);
+        }
+        if window.1 < limit {
+            window.1 += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Everything the handlers and middleware share.
+#[derive(Clone)]
+struct AppState {
+    config: Arc<Config>,
+    metrics: Arc<Metrics>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl FromRef<AppState> for Arc<Metrics> {
+    fn from_ref(state: &AppState) -> Self {
+        state.metrics.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Config> {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+/// Answers 429 Too Many Requests once the configured number of requests per second is reached.
+async fn limit_rate(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    match state.config.rate_limit {
+        Some(limit) if !state.limiter.try_acquire(limit) => StatusCode::TOO_MANY_REQUESTS.into_response(),
+        _ => next.run(request).await,
+    }
+}
+
+/// Lets browsers on the configured origins call the server; origins that are not valid header
+/// values are reported and left out.
+fn cors_layer(origins: &[String]) -> CorsLayer {
+    let origins: Vec<HeaderValue> = origins
+        .iter()
+        .filter_map(|origin| match HeaderValue::from_str(origin) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                eprintln!("Ignoring invalid CORS origin {:?}", origin);
+                None
+            }
+        })
+        .collect();
+    CorsLayer::new().allow_origin(AllowOrigin::list(origins)).allow_methods(Any).allow_headers(Any)
+}
+
+fn app(config: Arc<Config>) -> Router {
+    let state = AppState {
+        config: config.clone(),
+        metrics: Arc::new(Metrics::default()),
+        limiter: Arc::new(RateLimiter::new()),
+    };
+    let router = Router::new()
+        .route("/", get(hello))
+        .route("/version", get(version))
+        .route("/health", get(health))
+        .route("/ws", get(ws))
+        .route("/auction/combine", post(combine_auction))
+        .route("/shapes/svg", post(shapes_svg))
+        .route("/area", post(area))
+        .route("/coupons", get(coupons))
+        .route("/coupons.csv", get(coupons_csv))
+        .route("/metrics", get(metrics))
+        // A route layer only sees matched requests, so every label is a known route
+        .route_layer(middleware::from_fn_with_state(state.metrics.clone(), track_metrics))
+        .layer(middleware::from_fn_with_state(state.clone(), limit_rate))
+        .with_state(state);
+    if config.cors_origins.is_empty() {
+        router
+    } else {
+        router.layer(cors_layer(&config.cors_origins))
+    }
+}
+
 #[tokio::main]
 async fn main() {
-    let app = Router::new()
-        .route("/", get(hello));
+    let args = Args::parse();
+    let mut config = match Config::load(Path::new(CONFIG_FILE)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Could not load {}: {}", CONFIG_FILE, e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(port) = args.port {
+        config.port = port;
+    }
+    let address = match config.socket_addr() {
+        Ok(address) => address,
+        Err(e) => {
+            eprintln!("Could not resolve the address to listen on: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let config = Arc::new(config);
+
+    let listener = match tokio::net::TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Could not listen on {}: {}", address, e);
+            std::process::exit(1);
+        }
+    };
+    // Port 0 lets the OS pick one, so report the address actually bound
+    let address = listener.local_addr().unwrap_or(address);
+    println!("Server running on http://{}", address);
+
+    axum::serve(listener, app(config)).with_graceful_shutdown(shutdown_signal()).await.unwrap();
+    println!("Server stopped");
+}
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await.unwrap();
-    println!("Server running on http://127.0.0.1:3000");
+/// Completes on Ctrl+C or, on Unix, SIGTERM. The server then stops accepting connections and
+/// lets the requests in flight finish.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            eprintln!("Could not listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                eprintln!("Could not listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-    axum::serve(listener, app).await.unwrap();
-}
\ No newline at end of file
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
+    println!("Shutting down, finishing requests in flight");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    fn test_app() -> Router {
+        app(Arc::new(Config::default()))
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    async fn get_request(app: Router, uri: &str) -> Response {
+        app.oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    async fn post_json(app: Router, uri: &str, json: &str) -> Response {
+        let request = Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json.to_string()))
+            .unwrap();
+        app.oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn version_reports_the_package_version() {
+        let response = get_request(test_app(), "/version").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(body["git_commit"].is_string() && body["build_timestamp"].is_string());
+    }
+
+    #[tokio::test]
+    async fn health_answers_ok() {
+        let response = get_request(test_app(), "/health").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_text(response).await, r#"{"status":"ok"}"#);
+    }
+
+    #[tokio::test]
+    async fn websocket_echoes_until_bye() {
+        use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, test_app()).await.unwrap() });
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{address}/ws")).await.unwrap();
+        socket.send(ClientMessage::Text("hello".to_string())).await.unwrap();
+        assert_eq!(socket.next().await.unwrap().unwrap(), ClientMessage::Text(format!("{ECHO_PREFIX}hello")));
+        socket.send(ClientMessage::Binary(vec![1, 2, 3])).await.unwrap();
+        assert_eq!(socket.next().await.unwrap().unwrap(), ClientMessage::Binary(vec![1, 2, 3]));
+        socket.send(ClientMessage::Text("bye".to_string())).await.unwrap();
+        assert!(matches!(socket.next().await.unwrap().unwrap(), ClientMessage::Close(_)));
+    }
+
+    /// One lot of an auction JSON dump.
+    fn auction_json(id: i64) -> String {
+        serde_json::json!([{
+            "Id": id, "AuctioneerID": "A", "Auction": "Spring", "AuctSessionID": 1,
+            "AuctSessionName": "Session", "GoedID": id, "Lotnr": id.to_string(), "Description": "Vase",
+            "LowEstimate": "€ 100", "HighEstimate": "€ 200", "Search": "", "ImageURL": "",
+            "datumTot": "2025-01-27T16:00:28+00:00", "LowEstimateNum": 100,
+        }])
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn combine_auction_joins_uploads_and_names_skipped_files() {
+        const BOUNDARY: &str = "lot-boundary";
+        let mut body = String::new();
+        for (name, content) in [("one.json", auction_json(1)), ("broken.json", "{".to_string()), ("two.json", auction_json(2))] {
+            body.push_str(&format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"files\"; filename=\"{name}\"\r\n\
+                 Content-Type: application/json\r\n\r\n{content}\r\n"
+            ));
+        }
+        body.push_str(&format!("--{BOUNDARY}--\r\n"));
+        let request = Request::post("/auction/combine")
+            .header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={BOUNDARY}"))
+            .body(Body::from(body))
+            .unwrap();
+
+        let response = test_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/csv");
+        assert_eq!(response.headers()[SKIPPED_FILES_HEADER], "broken.json");
+        let csv = body_text(response).await;
+        // The header row and one row per lot
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.starts_with("Id,"));
+    }
+
+    #[tokio::test]
+    async fn shapes_svg_renders_every_shape() {
+        let shapes = r#"[{"type": "circle", "radius": 2}, {"type": "rectangle", "width": 4, "height": 9}]"#;
+        let response = post_json(test_app(), "/shapes/svg", shapes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/svg+xml");
+        let svg = body_text(response).await;
+        assert_eq!(svg.matches("<circle").count(), 1);
+        assert_eq!(svg.matches("<rect").count(), 1);
+
+        let response = post_json(test_app(), "/shapes/svg", r#"[{"type": "circle", "radius": -1}]"#).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = post_json(test_app(), "/shapes/svg", r#"{"type": "circle"}"#).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn area_of_a_circle() {
+        let response = post_json(test_app(), "/area", r#"{"type": "circle", "radius": 2}"#).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        let area = body["area"].as_f64().unwrap() as f32;
+        assert!((area - std::f32::consts::PI * 4.0).abs() < 1e-4, "{}", area);
+    }
+
+    #[tokio::test]
+    async fn area_rejects_invalid_shapes_with_422() {
+        for body in [r#"{"type": "circle", "radius": -2}"#, r#"{"type": "blob"}"#, "{"] {
+            let response = post_json(test_app(), "/area", body).await;
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+            assert!(!body_text(response).await.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn metrics_count_requests_per_route() {
+        let app = test_app();
+        for _ in 0..2 {
+            get_request(app.clone(), "/health").await;
+        }
+        let response = get_request(app, "/metrics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = body_text(response).await;
+        assert!(text.contains("http_requests_total{route=\"/health\"} 2"), "{}", text);
+        assert!(text.contains("http_request_duration_seconds_count{route=\"/health\"} 2"));
+    }
+
+    #[tokio::test]
+    async fn coupons_returns_the_requested_count() {
+        let response = get_request(test_app(), "/coupons?length=8&count=25&initials=AB").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let coupons: Vec<String> = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(coupons.len(), 25);
+        assert!(coupons.iter().all(|coupon| coupon.len() == 8 && coupon.starts_with("AB")));
+        assert_eq!(coupons.iter().collect::<std::collections::HashSet<_>>().len(), 25);
+    }
+
+    #[tokio::test]
+    async fn coupons_rejects_impossible_requests_with_400() {
+        let too_many = format!("/coupons?count={}", MAX_COUPONS_PER_REQUEST + 1);
+        for uri in [too_many.as_str(), "/coupons?length=4&initials=LISA", "/coupons?length=2&count=5000&initials="] {
+            let response = get_request(test_app(), uri).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn coupons_csv_streams_an_attachment() {
+        let response = get_request(test_app(), "/coupons.csv?count=20").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/csv");
+        assert!(response.headers()[header::CONTENT_DISPOSITION].to_str().unwrap().starts_with("attachment"));
+        let csv = body_text(response).await;
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("Coupon"));
+        assert_eq!(lines.count(), 20);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_answers_429() {
+        let app = app(Arc::new(Config { rate_limit: Some(1), ..Config::default() }));
+        assert_eq!(get_request(app.clone(), "/health").await.status(), StatusCode::OK);
+        assert_eq!(get_request(app, "/health").await.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
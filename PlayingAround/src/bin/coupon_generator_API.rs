@@ -1,208 +1,127 @@
-//! Coupon generation and CSV writing module.
+//! Command-line front end of the async coupon generator in `playing_around::coupon_stream`.
 //!
-//! This module provides functionality to generate unique coupons and write them to a CSV format.
-//! It's designed to be efficient and suitable for use in a web API context.
-
-use csv::Writer;
-use futures::stream::Stream;
-use rand::prelude::*;
-use rayon::prelude::*;
-use std::collections::HashSet;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
-use thiserror::Error;
-use tokio::io::AsyncWrite;
-#[derive(Error, Debug)]
-pub enum CouponError {
-    /// Occurs when the initials length exceeds the total coupon length.
-    #[error("Initials length ({0}) cannot be greater than the total coupon length ({1})")]
-    InitialsTooLong(usize, u16),
-
-    /// Occurs when the requested number of coupons exceeds the possible unique combinations.
-    #[error("Cannot generate {0} unique coupons with the given length and character set. Maximum possible is {1}")]
-    TooManyCoupons(usize, u128),
-
-    /// Wraps CSV writing errors.
-    #[error("Failed to write CSV data: {0}")]
-    CsvWriteError(#[from] csv::Error),
-
-    /// Wraps I/O errors.
-    #[error("I/O error: {0}")]
-    IoError(#[from] std::io::Error),
+//! Generates coupons into coupons.csv with a manifest next to it, or checks an existing file.
+
+use clap::{Parser, Subcommand};
+use futures::stream::StreamExt;
+use std::path::{Path, PathBuf};
+use playing_around::coupon_stream::{coupon_stream, write_coupons_to_csv_file, DEFAULT_BUFFER_SIZE};
+use playing_around::coupons::{
+    entropy_bits, estimate, group_with_check, verify_file, verify_manifest, write_manifest, Charset,
+    CouponError, CHARSET_LEN, DEFAULT_MIN_ENTROPY_BITS, GROUP_SEPARATOR, MANIFEST_FILE,
+    SATURATION_WARNING,
+};
+/// Command-line options of the generator.
+#[derive(Parser)]
+#[command(about = "Generate unique coupon codes into coupons.csv")]
+struct Args {
+    /// Only print the capacity estimate, without generating anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Split each coupon into groups of this many characters, each followed by a check character
+    #[arg(long, value_name = "SIZE")]
+    group_size: Option<usize>,
+
+    /// Warn when the random part of each coupon carries fewer bits of entropy than this
+    #[arg(long, default_value_t = DEFAULT_MIN_ENTROPY_BITS)]
+    min_entropy: f64,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-/// The character set used for generating coupons.
-const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
-/// The length of the character set.
-const CHARSET_LEN: usize = CHARSET.len();
-
-lazy_static::lazy_static! {
-    /// A lookup table for fast character conversion.
-    static ref CHAR_LOOKUP: [char; 256] = {
-        let mut lookup = ['\0'; 256];
-        for (_i, &b) in CHARSET.iter().enumerate() {
-            lookup[b as usize] = b as char;
-        }
-        lookup
-    };
+#[derive(Subcommand)]
+enum Command {
+    /// Check every coupon of an existing CSV file instead of generating new ones
+    VerifyFile {
+        /// The CSV file to check, with a "Coupon" header
+        path: PathBuf,
+        /// The expected total length of each coupon
+        #[arg(long, default_value_t = 10)]
+        len: u16,
+        /// The expected prefix of each coupon
+        #[arg(long, default_value = "LISA")]
+        initials: String,
+    },
+    /// Check that a coupon CSV file still holds exactly the batch its manifest describes
+    VerifyManifest {
+        /// The manifest written next to the CSV file
+        manifest: PathBuf,
+        /// The CSV file to check, with a "Coupon" header
+        path: PathBuf,
+    },
 }
 
-/// Generates a single coupon.
-///
-/// This function is used internally by the coupon generator.
-///
-/// # Arguments
-///
-/// * `rng` - A mutable reference to a `SmallRng` for random number generation.
-/// * `code_len` - The length of the random part of the coupon.
-/// * `initials` - The initials to prepend to the coupon.
-///
-/// # Returns
-///
-/// A `String` containing the generated coupon.
-fn generate_coupon(rng: &mut SmallRng, code_len: usize, initials: &str) -> String {
-    let mut coupon = String::with_capacity(initials.len() + code_len);
-    coupon.push_str(initials);
-    let mut buffer = vec![0u8; code_len];
-    rng.fill_bytes(&mut buffer);
-    for &byte in buffer.iter() {
-        let index = byte as usize % CHARSET_LEN;
-        coupon.push(CHAR_LOOKUP[CHARSET[index] as usize]);
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let charset = Charset::alphanumeric();
+
+    match &args.command {
+        Some(Command::VerifyFile { path, len, initials }) => {
+            let report = verify_file(path, *len, initials, &charset)?;
+            println!("{} valid, {} invalid coupons in {}", report.valid, report.invalid, path.display());
+            for (row, code, reason) in &report.offending {
+                println!("  row {}: {:?} {}", row, code, reason);
+            }
+            return Ok(());
+        }
+        Some(Command::VerifyManifest { manifest, path }) => {
+            if verify_manifest(manifest, path)? {
+                println!("{} matches {}", path.display(), manifest.display());
+            } else {
+                println!("{} does NOT match {}", path.display(), manifest.display());
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        None => {}
     }
-    coupon
-}
 
-/// Generates a stream of unique coupons.
-///
-/// This function returns a `Stream` that yields unique coupons. It's designed to be memory-efficient
-/// and suitable for use in a web API context.
-///
-/// # Arguments
-///
-/// * `len` - The total length of each coupon.
-/// * `number_coupons` - The number of unique coupons to generate.
-/// * `initials` - The initials to prepend to each coupon.
-///
-/// # Returns
-///
-/// A `Result` containing either a `Stream` of `Result<String, CouponError>` or a `CouponError`.
-///
-/// # Errors
-///
-/// Returns `CouponError::InitialsTooLong` if the initials are longer than the specified coupon length.
-/// Returns `CouponError::TooManyCoupons` if the requested number of coupons exceeds the possible unique combinations.
-pub fn coupon_generator(
-    len: u16,
-    number_coupons: usize,
-    initials: &str,
-) -> Result<impl Stream<Item = Result<String, CouponError>>, CouponError> {
-    let initial_len = initials.len();
-    let code_len = len as usize - initial_len;
-    if initial_len > len as usize {
-        return Err(CouponError::InitialsTooLong(initial_len, len));
+    let estimate = estimate(6, 1_000_000, CHARSET_LEN);
+    println!(
+        "{} of {} possible codes ({:.4}% saturation), ~{:.2}% extra draws for collisions",
+        1_000_000,
+        estimate.max_combinations,
+        estimate.saturation * 100.0,
+        estimate.collision_overhead * 100.0
+    );
+    if !estimate.feasible {
+        println!("Warning: there are not enough unique codes for this request");
+    } else if estimate.high_saturation {
+        println!("Warning: saturation is above {}%, generation will be slow", SATURATION_WARNING * 100.0);
     }
-    let max_combinations = (CHARSET_LEN as u128).pow(code_len as u32);
-    if number_coupons > max_combinations as usize {
-        return Err(CouponError::TooManyCoupons(number_coupons, max_combinations));
+    let bits = entropy_bits(6, CHARSET_LEN);
+    println!("Each coupon carries {:.1} bits of entropy", bits);
+    if bits < args.min_entropy {
+        println!(
+            "Warning: entropy is below {} bits, coupons could be guessed by brute force",
+            args.min_entropy
+        );
+    }
+    if args.dry_run {
+        return Ok(());
     }
 
-    let coupons = Arc::new(parking_lot::Mutex::new(HashSet::with_capacity(number_coupons)));
-    let counter = Arc::new(AtomicUsize::new(0));
-    let initials = initials.to_string();
-
-    Ok(futures::stream::unfold(
-        (coupons, counter, initials, code_len, number_coupons),
-        move |(coupons, counter, initials, code_len, number_coupons)| {
-            async move {
-                if counter.load(Ordering::SeqCst) >= number_coupons {
-                    None
-                } else {
-                    let mut rng = SmallRng::from_entropy();
-                    let coupon = loop {
-                        let new_coupon = generate_coupon(&mut rng, code_len, &initials);
-                        let mut set = coupons.lock();
-                        if set.insert(new_coupon.clone()) {
-                            break new_coupon;
-                        }
-                    };
-                    counter.fetch_add(1, Ordering::SeqCst);
-                    Some((Ok(coupon), (coupons, counter, initials, code_len, number_coupons)))
-                }
-            }
-        },
-    ))
-}
-
-/// Writes coupons to a CSV format.
-///
-/// This function takes a stream of coupons and writes them to the provided `AsyncWrite` in CSV format.
-///
-/// # Arguments
-///
-/// * `writer` - An `AsyncWrite` to which the CSV data will be written.
-/// * `coupons` - A `Stream` of `Result<String, CouponError>` representing the coupons to be written.
-///
-/// # Returns
-///
-/// A `Result<(), CouponError>` indicating success or failure of the operation.
-///
-/// # Errors
-///
-/// This function will return an error if there are issues writing to the CSV or if the input stream yields an error.
-// pub async fn write_coupons_to_csv<W: AsyncWrite + Unpin>(
-//     writer: W,
-//     coupons: impl Stream<Item = Result<String, CouponError>>,
-// ) -> Result<(), CouponError> {
-//     use tokio::io::AsyncWriteExt;
-
-//     let mut csv_writer = csv_async::AsyncWriter::from_writer(writer);
-//     csv_writer.write_record(&["Coupon"]).await?;
-
-//     tokio::pin!(coupons);
-//     while let Some(coupon_result) = coupons.next().await {
-//         let coupon = coupon_result?;
-//         csv_writer.write_record(&[&coupon]).await?;
-//     }
-
-//     csv_writer.flush().await?;
-//     Ok(())
-// }
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Start timing the coupon generation
+    // Start timing the coupon generation and CSV writing
     let start = std::time::Instant::now();
 
     // Generate coupons with total length 10, 1,000,000 coupons, and initials "LISA"
-    let coupons = coupon_generator(10, 1_000_000, "LISA")?;
-    let generation_duration = start.elapsed(); // Measure time taken
-
-    println!(
-        "Generated {} coupons in {:?}",
-        coupons.len(),
-        generation_duration
-    );
-    println!("First few coupons: {:?}", &coupons[..5]); // Display first few coupons
-
-    // Start timing the CSV writing
-    let csv_start = std::time::Instant::now();
-    write_coupons_to_csv(&coupons, "coupons.csv")?;
-    let csv_duration = csv_start.elapsed(); // Measure time taken
+    let coupons = coupon_stream(10, 1_000_000, "LISA", charset.clone(), DEFAULT_BUFFER_SIZE)?;
+    match args.group_size {
+        Some(group_size) => {
+            let group_charset = charset.clone();
+            let grouped = coupons.map(move |coupon| {
+                Ok::<_, CouponError>(group_with_check(&coupon?, group_size, GROUP_SEPARATOR, &group_charset)?)
+            });
+            write_coupons_to_csv_file("coupons.csv", grouped).await?;
+        }
+        None => write_coupons_to_csv_file("coupons.csv", coupons).await?,
+    }
 
-    println!("Wrote coupons to CSV in {:?}", csv_duration);
+    println!("Generated and wrote coupons to CSV in {:?}", start.elapsed());
+    let manifest = write_manifest(Path::new("coupons.csv"), 10, "LISA")?;
+    println!("Wrote {} for {} coupons, sha256 {}", MANIFEST_FILE, manifest.count, manifest.sha256);
     Ok(())
 }
-// Example usage in an API context (using actix-web):
-//
-// ```
-// #[get("/generate_coupons")]
-// async fn generate_coupons(
-//     query: web::Query<CouponParams>,
-// ) -> Result<HttpResponse, actix_web::Error> {
-//     let coupons = coupon_generator(query.length, query.count, &query.initials)?;
-//     let mut buffer = Vec::new();
-//     write_coupons_to_csv(&mut buffer, coupons).await?;
-//     Ok(HttpResponse::Ok()
-//         .content_type("text/csv")
-//         .body(buffer))
-// }
-// ```
\ No newline at end of file
@@ -1,175 +1,91 @@
-/// A Rust program to generate unique coupon codes with specified initials and code length.
-/// The program generates a specified number of unique coupons, writes them to a CSV file,
-/// and uses concurrency for efficient generation.
-
-use rand::prelude::*; // Import random number generation traits and functions
-use std::collections::HashSet;
-use std::fs::File;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
-use thiserror::Error;
-use csv::Writer;
-
-/// Custom error type for the coupon generation process.
-#[derive(Error, Debug)]
-enum CouponError {
-    /// Error when the initials length exceeds the total coupon length.
-    #[error("Initials length ({0}) cannot be greater than the total coupon length ({1})")]
-    InitialsTooLong(usize, u16),
-
-    /// Error when the requested number of coupons exceeds the maximum possible combinations.
-    #[error(
-        "Cannot generate {0} unique coupons with the given length and character set. Maximum possible is {1}"
-    )]
-    TooManyCoupons(usize, u128),
-
-    /// Error when writing to the CSV file fails.
-    #[error("Failed to write to CSV file: {0}")]
-    CsvWriteError(#[from] csv::Error),
-
-    /// Error when creating the output file fails.
-    #[error("Failed to create file: {0}")]
-    FileCreationError(#[from] std::io::Error),
+//! A Rust program to generate unique coupon codes with specified initials and code length.
+//! The program generates a specified number of unique coupons, writes them to a CSV file,
+//! and uses concurrency for efficient generation.
+
+use clap::Parser;
+use playing_around::coupons::{
+    append_check_character, coupon_generator, coupon_generator_from_mask, coupon_generator_resumable,
+    coupon_generator_to_csv, coupon_generator_to_files, coupon_generator_with_check,
+    write_coupons_to_files, write_coupons_to_path, Charset, CharsetOptions, Mask,
+    DEFAULT_CHECKPOINT_INTERVAL,
+};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Command-line options of the generator.
+#[derive(Parser)]
+#[command(about = "Generate unique coupon codes into coupons.csv")]
+struct Args {
+    /// Keep progress in this file so an interrupted run resumes where it stopped
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// Number of new coupons generated between two checkpoint flushes
+    #[arg(long, default_value_t = DEFAULT_CHECKPOINT_INTERVAL)]
+    checkpoint_interval: usize,
+
+    /// Characters of the random part: alphanumeric, mixed-case, uppercase, digits, crockford, or
+    /// the characters themselves, e.g. ABC123
+    #[arg(long, default_value = "alphanumeric")]
+    charset: Charset,
+
+    /// Leave out characters that are easily confused on paper (0/O, 1/I/l, 5/S, 8/B)
+    #[arg(long)]
+    exclude_ambiguous: bool,
+
+    /// End each coupon with a check character that catches a single mistyped character
+    #[arg(long)]
+    check_character: bool,
+
+    /// Seed the generator so the same seed writes the same coupons again
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Write each coupon to the CSV as soon as it is generated instead of collecting them first,
+    /// which about halves the memory used
+    #[arg(long, conflicts_with_all = ["checkpoint", "check_character", "seed"])]
+    stream: bool,
+
+    /// Lay the coupons out by a mask instead, e.g. 'LISA-####-AAAA': `#` is a digit, a run of
+    /// `A`s is letters, `{L}`, `{D}` and `{A}` are a letter, a digit and a letter or digit, `\`
+    /// makes the next character literal and everything else is copied
+    #[arg(long, conflicts_with_all = ["checkpoint", "check_character", "stream", "charset", "exclude_ambiguous"])]
+    mask: Option<Mask>,
+
+    /// Add an ExpiresAt column with the date this many days from today
+    #[arg(long)]
+    valid_days: Option<u32>,
+
+    /// Split the output into coupons_0001.csv, coupons_0002.csv, ... of at most this many rows
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    rows_per_file: Option<u64>,
+
+    /// Compress the output with gzip, writing coupons.csv.gz
+    #[arg(long)]
+    gzip: bool,
 }
 
-/// The character set used for generating the coupon codes.
-const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
-
-/// The length of the character set.
-const CHARSET_LEN: usize = CHARSET.len();
-
-lazy_static::lazy_static! {
-    /// A lookup table for mapping byte values to their corresponding characters.
-    /// Initialized once at runtime for efficient character mapping.
-    static ref CHAR_LOOKUP: [char; 256] = {
-        let mut lookup = ['\0'; 256];
-        for &b in CHARSET.iter() {
-            lookup[b as usize] = b as char;
-        }
-        lookup
-    };
-}
-
-/// Generates a single coupon code with the specified code length and initials.
-///
-/// # Arguments
-///
-/// * `rng` - A mutable reference to a random number generator.
-/// * `code_len` - The length of the random part of the coupon code.
-/// * `initials` - The initials to prefix the coupon code with.
-///
-/// # Returns
-///
-/// A `String` representing the generated coupon code.
-fn generate_coupon(rng: &mut SmallRng, code_len: usize, initials: &str) -> String {
-    // Pre-allocate a string with the required capacity for efficiency
-    let mut coupon = String::with_capacity(initials.len() + code_len);
-    coupon.push_str(initials); // Add the initials to the coupon code
-
-    // Buffer to hold random bytes
-    let mut buffer = vec![0u8; code_len];
-    rng.fill_bytes(&mut buffer); // Fill the buffer with random bytes
-
-    // Convert random bytes to characters from CHARSET and append to coupon
-    for &byte in buffer.iter() {
-        let index = byte as usize % CHARSET_LEN;
-        coupon.push(CHAR_LOOKUP[CHARSET[index] as usize]);
+impl Args {
+    /// Where the coupons are written, before any `--rows-per-file` numbering.
+    fn output(&self) -> &'static str {
+        if self.gzip { "coupons.csv.gz" } else { "coupons.csv" }
     }
-
-    coupon
 }
 
-/// Generates a specified number of unique coupon codes.
-///
-/// # Arguments
-///
-/// * `len` - The total length of each coupon code (initials + random code).
-/// * `number_coupons` - The total number of unique coupons to generate.
-/// * `initials` - The initials to prefix each coupon code with.
-///
-/// # Returns
-///
-/// A `Result` containing a vector of unique coupon codes or a `CouponError`.
-fn coupon_generator(
-    len: u16,
-    number_coupons: usize,
-    initials: &str,
-) -> Result<Vec<String>, CouponError> {
-    let initial_len = initials.len();
-    let code_len = len as usize - initial_len;
-
-    // Check if the initials length exceeds the total coupon length
-    if initial_len > len as usize {
-        return Err(CouponError::InitialsTooLong(initial_len, len));
-    }
-
-    // Calculate the maximum possible combinations based on CHARSET and code length
-    let max_combinations = (CHARSET_LEN as u128).pow(code_len as u32);
-    if number_coupons > max_combinations as usize {
-        return Err(CouponError::TooManyCoupons(number_coupons, max_combinations));
-    }
-
-    // Use a thread-safe set to store unique coupons
-    let coupons = Arc::new(parking_lot::Mutex::new(HashSet::with_capacity(number_coupons)));
-    let counter = Arc::new(AtomicUsize::new(0));
-
-    // Use Rayon for parallel execution
-    rayon::scope(|s| {
-        for _ in 0..rayon::current_num_threads() {
-            let coupons = Arc::clone(&coupons);
-            let counter = Arc::clone(&counter);
-            s.spawn(move |_| {
-                let mut rng = SmallRng::from_entropy();
-                loop {
-                    // Atomically get the next number to process
-                    let my_number = counter.fetch_add(1, Ordering::SeqCst);
-                    if my_number >= number_coupons {
-                        break;
-                    }
-
-                    // Generate unique coupons
-                    loop {
-                        let coupon = generate_coupon(&mut rng, code_len, initials);
-                        let mut set = coupons.lock();
-                        if set.insert(coupon) {
-                            break; // Break if the coupon is unique
-                        }
-                    }
-                }
-            });
+/// Writes the coupons to coupons.csv, or split over numbered files with `--rows-per-file`.
+fn write_output(coupons: &[String], args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    match args.rows_per_file {
+        Some(rows) => {
+            let path = Path::new(args.output());
+            let files = write_coupons_to_files(coupons, path, rows as usize, args.valid_days)?;
+            println!("Split the coupons over {} files", files.len());
+        }
+        None => {
+            write_coupons_to_path(coupons, args.output(), args.valid_days)?;
         }
-    });
-
-    // Collect the generated coupons into a vector
-    Ok(Arc::try_unwrap(coupons)
-        .unwrap()
-        .into_inner()
-        .into_iter()
-        .collect())
-}
-
-/// Writes the list of coupons to a CSV file.
-///
-/// # Arguments
-///
-/// * `coupons` - A slice of coupon codes to write to the file.
-/// * `filename` - The name of the output CSV file.
-///
-/// # Returns
-///
-/// A `Result` indicating success or a `CouponError`.
-fn write_coupons_to_csv(coupons: &[String], filename: &str) -> Result<(), CouponError> {
-    let file = File::create(filename)?; // Create or overwrite the CSV file
-    let mut writer = Writer::from_writer(file);
-
-    writer.write_record(&["Coupon"])?; // Write the header
-
-    // Write each coupon code to the CSV file
-    for coupon in coupons {
-        writer.write_record(&[coupon])?;
     }
-
-    writer.flush()?; // Ensure all data is written to the file
     Ok(())
 }
 
@@ -181,11 +97,76 @@ fn write_coupons_to_csv(coupons: &[String], filename: &str) -> Result<(), Coupon
 ///
 /// A `Result` indicating success or an error.
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let charset = args.charset.with_options(CharsetOptions { exclude_ambiguous: args.exclude_ambiguous })?;
+
     // Start timing the coupon generation
     let start = std::time::Instant::now();
 
+    if let Some(mask) = &args.mask {
+        let coupons = coupon_generator_from_mask(mask, 1_000_000, args.seed)?;
+        println!("Generated {} coupons in {:?}", coupons.len(), start.elapsed());
+        println!("First few coupons: {:?}", &coupons[..5]);
+        write_output(&coupons, &args)?;
+        return Ok(());
+    }
+
+    if args.stream {
+        match args.rows_per_file {
+            Some(rows) => {
+                let path = Path::new(args.output());
+                let files = coupon_generator_to_files(
+                    10,
+                    1_000_000,
+                    "LISA",
+                    &charset,
+                    path,
+                    rows as usize,
+                    args.valid_days,
+                )?;
+                println!("Split the coupons over {} files", files.len());
+            }
+            None => {
+                let file = std::io::BufWriter::new(fs::File::create(args.output())?);
+                if args.gzip {
+                    let mut encoder = GzEncoder::new(file, Compression::default());
+                    coupon_generator_to_csv(10, 1_000_000, "LISA", &charset, &mut encoder, args.valid_days)?;
+                    // Without the trailer written by finish the archive reads as truncated
+                    encoder.finish()?;
+                } else {
+                    coupon_generator_to_csv(10, 1_000_000, "LISA", &charset, file, args.valid_days)?;
+                }
+            }
+        }
+        println!("Generated and wrote 1000000 coupons to CSV in {:?}", start.elapsed());
+        return Ok(());
+    }
+
     // Generate coupons with total length 10, 1,000,000 coupons, and initials "LISA"
-    let coupons = coupon_generator(10, 1_000_000, "LISA")?;
+    let coupons = match (&args.checkpoint, args.check_character) {
+        (Some(checkpoint), check_character) => {
+            // The checkpoint keeps the coupons without their check character
+            let coupons = coupon_generator_resumable(
+                if check_character { 9 } else { 10 },
+                1_000_000,
+                "LISA",
+                &charset,
+                checkpoint,
+                args.checkpoint_interval,
+                args.seed,
+            )?;
+            if check_character {
+                coupons
+                    .into_iter()
+                    .map(|coupon| append_check_character(coupon, &charset))
+                    .collect::<Result<_, _>>()?
+            } else {
+                coupons
+            }
+        }
+        (None, true) => coupon_generator_with_check(10, 1_000_000, "LISA", &charset, args.seed)?,
+        (None, false) => coupon_generator(10, 1_000_000, "LISA", &charset, args.seed)?,
+    };
     let generation_duration = start.elapsed(); // Measure time taken
 
     println!(
@@ -197,9 +178,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Start timing the CSV writing
     let csv_start = std::time::Instant::now();
-    write_coupons_to_csv(&coupons, "coupons.csv")?;
+    write_output(&coupons, &args)?;
     let csv_duration = csv_start.elapsed(); // Measure time taken
 
     println!("Wrote coupons to CSV in {:?}", csv_duration);
+
+    // The run is complete, so there is nothing left to resume
+    if let Some(checkpoint) = &args.checkpoint {
+        fs::remove_file(checkpoint)?;
+    }
     Ok(())
 }
@@ -1,6 +1,6 @@
 /*
-Convert strings to pig latin. The first consonant of each word is moved to the end of the word and ay is added,
-so first becomes irst-fay. Words that start with a vowel have hay added to the end instead (apple becomes apple-hay).
+Convert strings to pig latin. The consonants a word starts with are moved to the end of the word and ay is added,
+so first becomes irst-fay and string becomes ing-stray. Words that start with a vowel have hay added to the end instead (apple becomes apple-hay).
 Keep in mind the details about UTF-8 encoding!
 */
 //Helper to find out if the first letter is consonant
@@ -10,43 +10,134 @@ Keep in mind the details about UTF-8 encoding!
 // }
 
 
-fn word_translation (word:&str) -> String {
-    let mut word_without_first_char = String::new();
-    for i in word.chars().enumerate(){
-        //print!("{}",i.0==0);
-        if i.0 == 0{continue;}
-        //print!("-----\n{}",i.0);
-        //print!("\n{}",word_without_first_char);
-        word_without_first_char = format!("{}{}",word_without_first_char,i.1)
-                
-    }
-    //print!("{}\n\n",word_without_first_char);
-    word_without_first_char
-}
-fn is_first_letter_consonant(word: &str) -> (bool,char) {
-    let consonants: [char; 21] = [
-        'b', 'c', 'd', 'f', 'g', 'h', 'j', 'k', 'l', 'm', 'n', 'p', 'q', 'r', 's', 't', 'v', 'w',
-        'x', 'y', 'z',
-    ];
-    let first_letter = word.chars().next().unwrap();
-    (consonants.contains(&first_letter),first_letter)
-}
-//Helper to find the position of the the first consonant in a string
-// fn find_first_consonant(word: &str) {}
+const CONSONANTS: [char; 21] = [
+    'b', 'c', 'd', 'f', 'g', 'h', 'j', 'k', 'l', 'm', 'n', 'p', 'q', 'r', 's', 't', 'v', 'w',
+    'x', 'y', 'z',
+];
+//The table is lowercase, so "Lisa" has to be looked up as 'l'
+fn is_consonant(letter: char) -> bool {
+    CONSONANTS.contains(&letter.to_ascii_lowercase())
+}
+//None for an empty word, which has no first letter
+fn is_first_letter_consonant(word: &str) -> Option<(bool,char)> {
+    let first_letter = word.chars().next()?;
+    Some((is_consonant(first_letter),first_letter))
+}
+//Helper to find the position of the first vowel, i.e. the byte length of the leading consonants.
+//A leading "qu" counts as one consonant, so quiet -> iet-quay, and y is only a consonant as the
+//first letter: yellow -> ellow-yay but rhythm -> ythm-rhay.
+fn consonant_cluster_len(word: &str) -> usize {
+    let mut letters = word.char_indices().peekable();
+    while let Some((i, letter)) = letters.next() {
+        if !is_consonant(letter) || (i > 0 && letter.eq_ignore_ascii_case(&'y')) {
+            return i;
+        }
+        if letter.eq_ignore_ascii_case(&'q') {
+            letters.next_if(|(_, next)| next.eq_ignore_ascii_case(&'u'));
+        }
+    }
+    word.len()
+}
+//Helper to uppercase the first letter, so a capitalized word stays capitalized: Hello -> Ello-hay
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => format!("{}{}", first.to_uppercase(), chars.as_str()),
+        None => String::new(),
+    }
+}
 //Takes a string and converts it to pig latin
 fn convert_to_pig(word: &str) -> String {
-    let first_letter:(bool,char) = is_first_letter_consonant(word);
-    if first_letter.0 == true{
-        //let dummy1 = word.clone()
-        format!("{}{}{}{}",word_translation(word),"-",first_letter.1,"ay")
+    let Some(first_letter) = is_first_letter_consonant(word) else {
+        return String::new();
+    };
+    if first_letter.0 {
+        let (cluster, rest) = word.split_at(consonant_cluster_len(word));
+        if first_letter.1.is_uppercase() {
+            format!("{}{}{}{}",capitalize(rest),"-",cluster.to_lowercase(),"ay")
+        } else {
+            format!("{}{}{}{}",rest,"-",cluster,"ay")
+        }
     }
     else{
         format!("{}{}",word,"-hay")
     }
 }
+//Translates every word of a sentence, keeping the punctuation around a word where it is:
+//"Hello, world!" -> "Ello-hay, orld-way!". Tokens that are not words, like 42, are kept as they are.
+fn translate_sentence(sentence: &str) -> String {
+    let mut translated: Vec<String> = Vec::new();
+    for token in sentence.split_whitespace() {
+        let word = token.trim_matches(|c: char| !c.is_alphabetic());
+        if word.is_empty() || !word.chars().all(char::is_alphabetic) {
+            translated.push(token.to_string());
+            continue;
+        }
+        //Whatever trim_start cut off is the leading punctuation
+        let start = token.len() - token.trim_start_matches(|c: char| !c.is_alphabetic()).len();
+        let end = start + word.len();
+        translated.push(format!("{}{}{}", &token[..start], convert_to_pig(word), &token[end..]));
+    }
+    translated.join(" ")
+}
+//Translates the arguments when there are any, e.g. `pig_latin "Hello, world!"`, otherwise every line
+//of stdin until EOF, so it works as a filter: `echo "hello world" | pig_latin`
 fn main() {
-    let wordy: &str = "lisa";
-    let wordy_translated = convert_to_pig(wordy);
-    print!("{} is {} in pig-latin",wordy,wordy_translated)
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        println!("{}",translate_sentence(&args.join(" ")));
+        return;
+    }
+    for line in std::io::stdin().lines() {
+        match line {
+            Ok(line) => println!("{}",translate_sentence(&line)),
+            Err(e) => {
+                eprintln!("Could not read stdin: {}",e);
+                std::process::exit(1);
+            }
+        }
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capitalized_words_stay_capitalized() {
+        assert_eq!(convert_to_pig("Hello"), "Ello-hay");
+        assert_eq!(convert_to_pig("Lisa"), "Isa-lay");
+        assert_eq!(convert_to_pig("Apple"), "Apple-hay");
+        assert_eq!(convert_to_pig("apple"), "apple-hay");
+    }
+
+    #[test]
+    fn empty_word_does_not_panic() {
+        assert_eq!(is_first_letter_consonant(""), None);
+        assert_eq!(convert_to_pig(""), "");
+    }
+
+    #[test]
+    fn whole_consonant_cluster_moves() {
+        assert_eq!(convert_to_pig("first"), "irst-fay");
+        assert_eq!(convert_to_pig("glove"), "ove-glay");
+        assert_eq!(convert_to_pig("string"), "ing-stray");
+        assert_eq!(convert_to_pig("quiet"), "iet-quay");
+    }
+
+    #[test]
+    fn y_is_a_consonant_only_as_the_first_letter() {
+        assert_eq!(convert_to_pig("yellow"), "ellow-yay");
+        assert_eq!(convert_to_pig("rhythm"), "ythm-rhay");
+        assert_eq!(convert_to_pig("my"), "y-may");
+    }
+
+    #[test]
+    fn sentences_keep_punctuation_and_non_words() {
+        assert_eq!(translate_sentence("Hello, world!"), "Ello-hay, orld-way!");
+        assert_eq!(translate_sentence("(string) quiet."), "(ing-stray) iet-quay.");
+        assert_eq!(translate_sentence("I have 42 cats"), "I-hay ave-hay 42 ats-cay");
+        assert_eq!(translate_sentence("  spaced   out  "), "aced-spay out-hay");
+        assert_eq!(translate_sentence(""), "");
+    }
+}
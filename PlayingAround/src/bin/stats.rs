@@ -1,42 +1,497 @@
 
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
 
-fn mode(numbers: &Vec<u32>) -> Option<u32> {
-    let mut mode_map: HashMap<u32, u32> = HashMap::new();
+// Every value that occurs most often, smallest first; empty for empty input
+fn modes(numbers: &[u32]) -> Vec<u32> {
+    let counts = count_occurrences(numbers);
+    let max_count = counts.values().copied().max().unwrap_or(0);
+    let mut modes: Vec<u32> = counts
+        .into_iter()
+        .filter(|&(_, count)| count == max_count)
+        .map(|(value, _)| value)
+        .collect();
+    modes.sort_unstable();
+    modes
+}
 
-    // Count occurrences of each number
+// How often each number occurs
+fn count_occurrences(numbers: &[u32]) -> HashMap<u32, usize> {
+    let mut mode_map: HashMap<u32, usize> = HashMap::new();
     for &v in numbers.iter() {
         let count = mode_map.entry(v).or_insert(0);
         *count += 1;
     }
+    mode_map
+}
+
+// Returns the mode together with how often it occurs.
+// When several values are equally common, the smallest of them is the mode.
+fn mode_with_count(numbers: &[u32]) -> Option<(u32, usize)> {
+    // Count occurrences of each number
+    let mode_map = count_occurrences(numbers);
 
     // Variables to track the maximum frequency and corresponding key (mode)
     let mut max_value = 0;
     let mut max_key = None;
 
-    // Find the key with the maximum value
+    // Find the key with the maximum value, preferring the smaller key on ties
     for (&key, &value) in mode_map.iter() {
-        if value > max_value {
+        if value > max_value || (value == max_value && max_key.is_some_and(|k| key < k)) {
             max_value = value;
             max_key = Some(key);  // Store the key of the maximum value
         }
     }
-fn mean(){
+    max_key.map(|key| (key, max_value))  // Return the mode, or None if the vector is empty
+}
+
+// The p-th percentile (0.0..=100.0), interpolating linearly between the two closest ranks
+fn percentile(numbers: &[u32], p: f64) -> Option<f64> {
+    let mut sorted: Vec<f64> = numbers.iter().map(|&v| v as f64).collect();
+    sorted.sort_unstable_by(f64::total_cmp);
+    percentile_of_sorted(&sorted, p)
+}
+
+// Same as percentile, for values that are already sorted
+fn percentile_of_sorted(sorted: &[f64], p: f64) -> Option<f64> {
+    if sorted.is_empty() || !(0.0..=100.0).contains(&p) {
+        return None;
+    }
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    let weight = rank - lower as f64;
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * weight)
+}
+
+// Interquartile range: Q3 - Q1, None when there are fewer than two numbers to spread
+fn iqr(numbers: &[u32]) -> Option<f64> {
+    if numbers.len() < 2 {
+        return None;
+    }
+    Some(percentile(numbers, 75.0)? - percentile(numbers, 25.0)?)
+}
+
+// Works for any numbers that convert losslessly to f64 (u32, f64, ...)
+fn mean<T: Copy + Into<f64>>(numbers: &[T]) -> Option<f64> {
+    if numbers.is_empty() {
+        return None;
+    }
+    let sum: f64 = numbers.iter().map(|&v| v.into()).sum();
+    Some(sum / numbers.len() as f64)
+}
+
+// The middle value once sorted, or the mean of the two middle values for an even count
+fn median(numbers: &[u32]) -> Option<f64> {
+    let mut sorted = numbers.to_vec();
+    sorted.sort_unstable();
+    let middle = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        len if len.is_multiple_of(2) => Some((f64::from(sorted[middle - 1]) + f64::from(sorted[middle])) / 2.0),
+        _ => Some(f64::from(sorted[middle])),
+    }
+}
+
+// Population variance: the mean of the squared distances to the mean
+fn variance<T: Copy + Into<f64>>(numbers: &[T]) -> Option<f64> {
+    let mean = mean(numbers)?;
+    let squared_distances: f64 = numbers.iter().map(|&v| (v.into() - mean).powi(2)).sum();
+    Some(squared_distances / numbers.len() as f64)
+}
+
+fn std_dev<T: Copy + Into<f64>>(numbers: &[T]) -> Option<f64> {
+    variance(numbers).map(f64::sqrt)
+}
+
+// sum(v * w) / sum(w); None when the lengths differ, the input is empty or the weights sum to zero
+fn weighted_mean(values: &[f64], weights: &[f64]) -> Option<f64> {
+    if values.is_empty() || values.len() != weights.len() {
+        return None;
+    }
+    let total_weight: f64 = weights.iter().sum();
+    if total_weight == 0.0 {
+        return None;
+    }
+    let weighted_sum: f64 = values.iter().zip(weights).map(|(v, w)| v * w).sum();
+    Some(weighted_sum / total_weight)
+}
+
+// Percentile bootstrap: the CI of the mean from the means of `resamples` samples drawn with
+// replacement. The same seed always gives the same interval.
+// None for empty input, no resamples, or a confidence outside of (0, 1)
+fn bootstrap_mean_ci(numbers: &[f64], resamples: usize, confidence: f64, seed: u64) -> Option<(f64, f64)> {
+    if numbers.is_empty() || resamples == 0 || !(confidence > 0.0 && confidence < 1.0) {
+        return None;
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut means: Vec<f64> = (0..resamples)
+        .map(|_| {
+            let sum: f64 = (0..numbers.len()).map(|_| numbers[rng.gen_range(0..numbers.len())]).sum();
+            sum / numbers.len() as f64
+        })
+        .collect();
+    means.sort_unstable_by(f64::total_cmp);
+    let tail = (1.0 - confidence) / 2.0 * 100.0;
+    Some((percentile_of_sorted(&means, tail)?, percentile_of_sorted(&means, 100.0 - tail)?))
+}
+
+// Everything above about one set of numbers at once
+#[derive(Debug)]
+struct Summary {
+    count: usize,
+    mean: f64,
+    median: f64,
+    modes: Vec<f64>,
+    min: f64,
+    max: f64,
+    variance: f64,
+}
+
+// None for empty input
+fn summarize(numbers: &[u32]) -> Option<Summary> {
+    Some(Summary {
+        count: numbers.len(),
+        mean: mean(numbers)?,
+        median: median(numbers)?,
+        modes: modes(numbers).into_iter().map(f64::from).collect(),
+        min: f64::from(*numbers.iter().min()?),
+        max: f64::from(*numbers.iter().max()?),
+        variance: variance(numbers)?,
+    })
+}
+
+// Summarizes any iterator of numbers without collecting it into a slice first: count, mean,
+// variance and extremes come from one streaming pass (Welford's algorithm). None for an empty
+// iterator.
+//
+// The median still needs every value, so the values are buffered as they stream by and sorted
+// once at the end; the modes, exact repeats of a value, come from the same sorted buffer.
+fn summarize_iter<I: IntoIterator<Item = f64>>(iter: I) -> Option<Summary> {
+    let values = iter.into_iter();
+    let mut buffered = Vec::with_capacity(values.size_hint().0);
+    let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+    let mut accumulator = StatsAccumulator::new();
+    for v in values {
+        accumulator.push(v);
+        min = min.min(v);
+        max = max.max(v);
+        buffered.push(v);
+    }
+    buffered.sort_unstable_by(f64::total_cmp);
+    // Runs of equal values in the sorted buffer, the longest of which are the modes
+    let runs: Vec<&[f64]> = buffered.chunk_by(|a, b| a == b).collect();
+    let longest = runs.iter().map(|run| run.len()).max()?;
+    Some(Summary {
+        count: accumulator.count(),
+        mean: accumulator.mean()?,
+        median: percentile_of_sorted(&buffered, 50.0)?,
+        modes: runs.iter().filter(|run| run.len() == longest).map(|run| run[0]).collect(),
+        min,
+        max,
+        variance: accumulator.variance()?,
+    })
+}
+
+// Running count, mean and population variance of values pushed one at a time, in constant
+// memory. Welford's update stays accurate where summing squares would cancel out.
+#[derive(Debug, Default)]
+struct StatsAccumulator {
+    count: usize,
+    mean: f64,
+    // Sum of the squared distances to the running mean
+    m2: f64,
+}
+
+impl StatsAccumulator {
+    fn new() -> Self {
+        StatsAccumulator::default()
+    }
+
+    fn push(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    // None until a value was pushed
+    fn mean(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.mean)
+    }
+
+    // None until a value was pushed
+    fn variance(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.m2 / self.count as f64)
+    }
+}
+
+// Standardizes each value to (x - mean) / std_dev, keeping the input order.
+// None for empty input or when all values are equal (zero variance)
+fn z_scores(numbers: &[f64]) -> Option<Vec<f64>> {
+    let mean = mean(numbers)?;
+    let std_dev = std_dev(numbers)?;
+    if std_dev == 0.0 {
+        return None;
+    }
+    Some(numbers.iter().map(|&v| (v - mean) / std_dev).collect())
+}
 
+// Scales each value into 0.0..=1.0 with (x - min) / (max - min).
+// None for empty input; all 0.0 when every value is equal, as there is no range to scale
+fn normalize_min_max(numbers: &[f64]) -> Option<Vec<f64>> {
+    let min = numbers.iter().copied().reduce(f64::min)?;
+    let max = numbers.iter().copied().reduce(f64::max)?;
+    let range = max - min;
+    if range == 0.0 {
+        return Some(vec![0.0; numbers.len()]);
+    }
+    Some(numbers.iter().map(|&v| (v - min) / range).collect())
 }
 
-fn median (){
-    
+// Running total; u64 so long series of u32 values cannot overflow
+fn cumulative_sum(numbers: &[u32]) -> Vec<u64> {
+    numbers
+        .iter()
+        .scan(0u64, |total, &v| {
+            *total += u64::from(v);
+            Some(*total)
+        })
+        .collect()
 }
 
-    max_key  // Return the mode, or None if the vector is empty
+// Largest value seen so far at each position
+fn running_max(numbers: &[u32]) -> Vec<u32> {
+    numbers
+        .iter()
+        .scan(0u32, |max, &v| {
+            *max = (*max).max(v);
+            Some(*max)
+        })
+        .collect()
 }
 
 fn main() {
     let numbers = vec![1, 2, 3, 4, 5, 6, 7, 2, 3, 4, 4, 4];
-    if let Some(mode_of_numbers) = mode(&numbers) {
-        println!("The mode is: {}", mode_of_numbers);
-    } else {
+    let modes_of_numbers = modes(&numbers);
+    if modes_of_numbers.is_empty() {
         println!("No mode found");
+    } else {
+        println!("The modes are: {:?}", modes_of_numbers);
+    }
+    // A tie keeps every value, and nothing has no mode at all
+    println!("The modes of [1, 1, 2, 2] are {:?}, of [] {:?}", modes(&[1, 1, 2, 2]), modes(&[]));
+
+    if let Some((value, count)) = mode_with_count(&numbers) {
+        println!("The mode {} occurs {} times", value, count);
+    }
+
+    if let (Some(median), Some(spread)) = (percentile(&numbers, 50.0), iqr(&numbers)) {
+        println!("The median is {} and the interquartile range is {}", median, spread);
+    }
+
+    if let Some(summary) = summarize(&numbers) {
+        println!(
+            "{} numbers from {} to {}: mean {:.2}, median {}, modes {:?}, variance {:.4}",
+            summary.count, summary.min, summary.max, summary.mean, summary.median, summary.modes, summary.variance
+        );
+    }
+    // An even count averages the two middle values
+    println!("The median of [4, 1, 3, 2] is {:?}, of [] {:?}", median(&[4, 1, 3, 2]), median(&[]));
+
+    println!("The cumulative sum is: {:?}", cumulative_sum(&numbers));
+    println!("The running maximum is: {:?}", running_max(&numbers));
+
+    let measurements: Vec<f64> = numbers.iter().map(|&v| v as f64).collect();
+    // Later measurements count more
+    let weights: Vec<f64> = (1..=measurements.len()).map(|i| i as f64).collect();
+    if let Some(weighted) = weighted_mean(&measurements, &weights) {
+        println!("The weighted mean is: {:.2}", weighted);
+    }
+    if let Some((low, high)) = bootstrap_mean_ci(&measurements, 1000, 0.95, 42) {
+        println!("95% bootstrap interval of the mean: {:.2} to {:.2}", low, high);
+    }
+    // Streams straight from the numbers, matching mean() and variance() on the slice
+    if let Some(summary) = summarize_iter(numbers.iter().map(|&v| v as f64)) {
+        println!(
+            "{} numbers from {} to {}: mean {:.2} (slice {:.2?}), variance {:.2} (slice {:.2?})",
+            summary.count,
+            summary.min,
+            summary.max,
+            summary.mean,
+            mean(&numbers),
+            summary.variance,
+            variance(&numbers)
+        );
+    }
+    // Large values with a small spread, where sum(x^2) - n * mean^2 loses every digit
+    let offset: Vec<f64> = measurements.iter().map(|v| v + 1e9).collect();
+    let mut accumulator = StatsAccumulator::new();
+    for &v in &offset {
+        accumulator.push(v);
+    }
+    if let (Some(running), Some(batch)) = (accumulator.variance(), variance(&offset)) {
+        println!(
+            "Accumulated {} values: mean {:.2}, variance {:.4} (batch {:.4}, equal within 1e-6: {})",
+            accumulator.count(),
+            accumulator.mean().unwrap_or_default(),
+            running,
+            batch,
+            (running - batch).abs() < 1e-6
+        );
+    }
+    if let Some(z) = z_scores(&measurements) {
+        println!("The z-scores are: {:.2?}", z);
+    }
+    if let Some(scaled) = normalize_min_max(&measurements) {
+        println!("Scaled to 0..=1: {:.2?}", scaled);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < EPSILON, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn modes_keep_every_tie_in_order() {
+        assert_eq!(modes(&[1, 2, 2, 3]), vec![2]);
+        assert_eq!(modes(&[2, 2, 1, 1, 3]), vec![1, 2]);
+        assert!(modes(&[]).is_empty());
+    }
+
+    #[test]
+    fn mode_with_count_prefers_the_smallest_tie() {
+        assert_eq!(mode_with_count(&[1, 2, 3, 4, 4, 4, 2]), Some((4, 3)));
+        assert_eq!(mode_with_count(&[5, 5, 3, 3]), Some((3, 2)));
+        assert_eq!(mode_with_count(&[]), None);
+    }
+
+    #[test]
+    fn summary_of_a_known_dataset() {
+        let summary = summarize(&[2, 4, 4, 4, 5, 5, 7, 9]).unwrap();
+        assert_eq!(summary.count, 8);
+        assert_close(summary.mean, 5.0);
+        assert_close(summary.median, 4.5);
+        assert_eq!(summary.modes, vec![4.0]);
+        assert_eq!((summary.min, summary.max), (2.0, 9.0));
+        assert_close(summary.variance, 4.0);
+        assert!(summarize(&[]).is_none());
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(&[3, 1, 2]), Some(2.0));
+        assert_eq!(median(&[4, 1, 3, 2]), Some(2.5));
+        assert_eq!(median(&[]), None);
+        assert_eq!(mean::<u32>(&[]), None);
+        assert_eq!(variance::<u32>(&[]), None);
+    }
+
+    #[test]
+    fn iqr_of_one_to_a_hundred() {
+        let numbers: Vec<u32> = (1..=100).collect();
+        assert_close(iqr(&numbers).unwrap(), 49.5);
+        assert_eq!(iqr(&[]), None);
+        assert_eq!(iqr(&[7]), None);
+    }
+
+    #[test]
+    fn weighted_mean_cases() {
+        let values = [1.0, 2.0, 3.0, 4.0];
+        assert_close(weighted_mean(&values, &[1.0; 4]).unwrap(), mean(&values).unwrap());
+        assert_close(weighted_mean(&values, &[0.0, 0.0, 0.0, 1.0]).unwrap(), 4.0);
+        assert_close(weighted_mean(&[10.0, 20.0], &[3.0, 1.0]).unwrap(), 12.5);
+        assert_eq!(weighted_mean(&values, &[1.0, 1.0]), None);
+        assert_eq!(weighted_mean(&values, &[0.0; 4]), None);
+        assert_eq!(weighted_mean(&[], &[]), None);
+    }
+
+    #[test]
+    fn bootstrap_interval_brackets_the_mean() {
+        let numbers = [2.0, 4.0, 4.0, 5.0, 7.0, 9.0, 3.0, 6.0];
+        let sample_mean = mean(&numbers).unwrap();
+        let (low, high) = bootstrap_mean_ci(&numbers, 2000, 0.95, 7).unwrap();
+        assert!(low < sample_mean && sample_mean < high, "{} not in {}..{}", sample_mean, low, high);
+        assert_eq!(bootstrap_mean_ci(&numbers, 2000, 0.95, 7), Some((low, high)));
+        assert_eq!(bootstrap_mean_ci(&[], 100, 0.95, 7), None);
+        assert_eq!(bootstrap_mean_ci(&numbers, 100, 1.5, 7), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn z_scores_have_zero_mean_and_unit_deviation() {
+        let numbers = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let z = z_scores(&numbers).unwrap();
+        assert_close(mean(&z).unwrap(), 0.0);
+        assert_close(std_dev(&z).unwrap(), 1.0);
+        // Order is preserved: 2 is the smallest value and 9 the largest
+        assert_close(z[0], -1.5);
+        assert_close(z[7], 2.0);
+        assert_eq!(z_scores(&[]), None);
+        assert_eq!(z_scores(&[3.0, 3.0]), None);
+    }
+
+    #[test]
+    fn min_max_normalization() {
+        assert_eq!(normalize_min_max(&[2.0, 4.0, 6.0]), Some(vec![0.0, 0.5, 1.0]));
+        assert_eq!(normalize_min_max(&[5.0]), Some(vec![0.0]));
+        assert_eq!(normalize_min_max(&[3.0, 3.0, 3.0]), Some(vec![0.0; 3]));
+        assert_eq!(normalize_min_max(&[]), None);
+    }
+
+    #[test]
+    fn scans_have_the_input_length() {
+        let numbers = [3, 1, 4, 1, 5, 9, 2, 6];
+        let sums = cumulative_sum(&numbers);
+        assert_eq!(sums.len(), numbers.len());
+        assert_eq!(sums.last(), Some(&numbers.iter().map(|&v| u64::from(v)).sum()));
+        let maxima = running_max(&numbers);
+        assert_eq!(maxima.len(), numbers.len());
+        assert!(maxima.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(maxima, vec![3, 3, 4, 4, 5, 9, 9, 9]);
+        assert!(cumulative_sum(&[]).is_empty());
+        assert!(running_max(&[]).is_empty());
+    }
+
+    #[test]
+    fn accumulator_matches_the_batch_functions() {
+        // Large values with a small spread, where summing squares would cancel out
+        let values: Vec<f64> = (1..=50).map(|v| 1e9 + f64::from(v)).collect();
+        let mut accumulator = StatsAccumulator::new();
+        assert_eq!(accumulator.mean(), None);
+        for &v in &values {
+            accumulator.push(v);
+        }
+        assert_eq!(accumulator.count(), values.len());
+        assert!((accumulator.mean().unwrap() - mean(&values).unwrap()).abs() < 1e-6);
+        assert!((accumulator.variance().unwrap() - variance(&values).unwrap()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn summarize_iter_matches_the_slice_version() {
+        let numbers: Vec<u32> = vec![3, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5, 1];
+        let streamed = summarize_iter(numbers.iter().map(|&v| f64::from(v))).unwrap();
+        let summary = summarize(&numbers).unwrap();
+        assert_eq!(streamed.count, summary.count);
+        assert_close(streamed.mean, summary.mean);
+        assert_close(streamed.median, summary.median);
+        assert_eq!(streamed.modes, summary.modes);
+        assert_eq!(streamed.modes, [1.0, 5.0]);
+        assert_eq!((streamed.min, streamed.max), (summary.min, summary.max));
+        assert_close(streamed.variance, summary.variance);
+
+        let range: Vec<u32> = (1..=20).collect();
+        let streamed = summarize_iter((1..=20).map(f64::from)).unwrap();
+        assert_close(streamed.median, summarize(&range).unwrap().median);
+        assert!(summarize_iter(std::iter::empty()).is_none());
+    }
+}
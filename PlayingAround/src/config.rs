@@ -0,0 +1,193 @@
+use std::fs;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Settings of the axum server, read from an optional TOML file and overridden by environment
+/// variables. Every field has a default, so a missing file or key is not an error.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The address to listen on (`HOST`).
+    pub host: String,
+    /// The port to listen on (`PORT`).
+    pub port: u16,
+    /// The most requests served per second, or no limit when unset (`RATE_LIMIT`).
+    pub rate_limit: Option<u32>,
+    /// Origins allowed to call the server from a browser; empty disables CORS (`CORS_ORIGINS`,
+    /// comma-separated).
+    pub cors_origins: Vec<String>,
+    /// What coupon endpoints generate when a request does not say.
+    pub coupons: CouponDefaults,
+}
+
+/// Default parameters of generated coupons.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct CouponDefaults {
+    /// The total length of each coupon (`COUPON_LEN`).
+    pub len: u16,
+    /// How many coupons are generated (`COUPON_COUNT`).
+    pub count: usize,
+    /// The prefix of each coupon (`COUPON_INITIALS`).
+    pub initials: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            host: "127.0.0.1".to_string(),
+            port: 3000,
+            rate_limit: None,
+            cors_origins: Vec::new(),
+            coupons: CouponDefaults::default(),
+        }
+    }
+}
+
+impl Default for CouponDefaults {
+    fn default() -> Self {
+        CouponDefaults { len: 10, count: 100, initials: "LISA".to_string() }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    /// The configuration file exists but could not be read.
+    #[error("failed to read config: {0}")]
+    Io(#[from] io::Error),
+
+    /// The configuration file is not valid TOML for a [`Config`].
+    #[error("invalid config: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    /// An environment variable override does not parse as the setting's type.
+    #[error("invalid value {1:?} for {0}")]
+    InvalidEnv(&'static str, String),
+
+    /// The configured host is not an IP address.
+    #[error("invalid host {0:?}, expected an IP address such as 127.0.0.1 or 0.0.0.0")]
+    InvalidHost(String),
+}
+
+impl Config {
+    /// Parses a configuration from TOML text; missing keys keep their defaults.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Reads `path` if it exists, then applies the overrides of the process environment.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let config = match fs::read_to_string(path) {
+            Ok(text) => Self::from_toml(&text)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Config::default(),
+            Err(e) => return Err(e.into()),
+        };
+        config.with_env(|key| std::env::var(key).ok())
+    }
+
+    /// The address the server binds to, from `host` and `port`.
+    pub fn socket_addr(&self) -> Result<SocketAddr, ConfigError> {
+        let ip: IpAddr = self.host.trim().parse().map_err(|_| ConfigError::InvalidHost(self.host.clone()))?;
+        Ok(SocketAddr::new(ip, self.port))
+    }
+
+    /// Overrides every setting whose variable `lookup` returns a value for.
+    pub fn with_env(mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        fn parse<T: std::str::FromStr>(key: &'static str, value: String) -> Result<T, ConfigError> {
+            value.trim().parse().map_err(|_| ConfigError::InvalidEnv(key, value))
+        }
+        if let Some(host) = lookup("HOST") {
+            self.host = host;
+        }
+        if let Some(port) = lookup("PORT") {
+            self.port = parse("PORT", port)?;
+        }
+        if let Some(rate_limit) = lookup("RATE_LIMIT") {
+            self.rate_limit = Some(parse("RATE_LIMIT", rate_limit)?);
+        }
+        if let Some(origins) = lookup("CORS_ORIGINS") {
+            self.cors_origins = origins
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        if let Some(len) = lookup("COUPON_LEN") {
+            self.coupons.len = parse("COUPON_LEN", len)?;
+        }
+        if let Some(count) = lookup("COUPON_COUNT") {
+            self.coupons.count = parse("COUPON_COUNT", count)?;
+        }
+        if let Some(initials) = lookup("COUPON_INITIALS") {
+            self.coupons.initials = initials;
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+        port = 8080
+        rate_limit = 50
+        cors_origins = ["https://example.com"]
+
+        [coupons]
+        initials = "SHOP"
+    "#;
+
+    #[test]
+    fn toml_keeps_defaults_for_missing_keys() {
+        let config = Config::from_toml(SAMPLE).unwrap();
+        assert_eq!(config.host, "127.0.0.1");
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.rate_limit, Some(50));
+        assert_eq!(config.cors_origins, ["https://example.com"]);
+        assert_eq!((config.coupons.len, config.coupons.count), (10, 100));
+        assert_eq!(config.coupons.initials, "SHOP");
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let env = |key: &str| match key {
+            "PORT" => Some("9000".to_string()),
+            "CORS_ORIGINS" => Some("https://a.example, ,https://b.example".to_string()),
+            "COUPON_COUNT" => Some("5".to_string()),
+            _ => None,
+        };
+        let config = Config::from_toml(SAMPLE).unwrap().with_env(env).unwrap();
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.rate_limit, Some(50));
+        assert_eq!(config.cors_origins, ["https://a.example", "https://b.example"]);
+        assert_eq!(config.coupons.count, 5);
+        assert_eq!(config.coupons.initials, "SHOP");
+        assert_eq!(config.socket_addr().unwrap(), "127.0.0.1:9000".parse().unwrap());
+    }
+
+    #[test]
+    fn invalid_values_are_reported() {
+        let env = |key: &str| (key == "PORT").then(|| "eighty".to_string());
+        assert!(matches!(
+            Config::default().with_env(env),
+            Err(ConfigError::InvalidEnv("PORT", value)) if value == "eighty"
+        ));
+        assert!(matches!(Config::from_toml("port = \"eighty\""), Err(ConfigError::Toml(_))));
+        let config = Config { host: "localhost".to_string(), ..Config::default() };
+        assert!(matches!(config.socket_addr(), Err(ConfigError::InvalidHost(_))));
+    }
+
+    #[test]
+    fn missing_file_gives_the_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config.port, Config::default().port);
+        // load also applies the process environment, so only the file handling is checked here
+        assert!(Config::load(&dir.path().join("config.toml")).is_ok());
+    }
+}
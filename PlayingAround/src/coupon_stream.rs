@@ -0,0 +1,309 @@
+//! Coupons generated on a blocking task and consumed as an async `Stream`, and writing such a
+//! stream as CSV, for servers and other async code.
+
+use crate::coupons::{
+    check_request, code_from_index, generate_coupon, generate_from_mask, max_combinations, Charset,
+    CouponError, IndexPermutation, Mask, DENSE_FRACTION,
+};
+use futures::stream::{Stream, StreamExt};
+use rand::prelude::*;
+use std::collections::HashSet;
+use std::path::Path;
+use tokio::io::AsyncWrite;
+use tokio::sync::mpsc;
+
+/// The default number of generated coupons that may wait in the buffer for the consumer.
+///
+/// 1024 coupons of a few bytes each keeps the buffer well under a megabyte while still
+/// letting the generator run ahead of a CSV writer flushing to disk or a socket.
+pub const DEFAULT_BUFFER_SIZE: usize = 1024;
+
+/// The most coupons the generating task hands over to the stream at once.
+const PRODUCER_BATCH: usize = 256;
+
+/// Runs `generate` on a blocking task until `number_coupons` unique coupons were sent.
+///
+/// The task owns the uniqueness `HashSet`, so no lock is shared with the consumer or held
+/// across an await. It sends the coupons in batches through a channel holding about
+/// `buffer_size` coupons; once the channel is full, generation pauses until the consumer
+/// catches up. Dropping the stream stops the producer.
+///
+/// The stream yields exactly `number_coupons` coupons: should the task stop early, the last
+/// item is `CouponError::GenerationStopped`, converted into the caller's error type.
+///
+/// Must be called from within a Tokio runtime.
+pub fn spawn_producer<F, E>(
+    number_coupons: usize,
+    buffer_size: usize,
+    mut generate: F,
+) -> impl Stream<Item = Result<String, E>>
+where
+    F: FnMut(&mut SmallRng) -> String + Send + 'static,
+    E: From<CouponError>,
+{
+    let batch_len = buffer_size.clamp(1, PRODUCER_BATCH);
+    let (sender, receiver) = mpsc::channel::<Vec<String>>((buffer_size / batch_len).max(1));
+
+    tokio::task::spawn_blocking(move || {
+        let mut rng = SmallRng::from_entropy();
+        let mut coupons = HashSet::with_capacity(number_coupons);
+        while coupons.len() < number_coupons {
+            // Vec::with_capacity may allocate more than asked, so loop on the target itself
+            let target = batch_len.min(number_coupons - coupons.len());
+            let mut batch = Vec::with_capacity(target);
+            while batch.len() < target {
+                let coupon = generate(&mut rng);
+                if coupons.insert(coupon.clone()) {
+                    batch.push(coupon);
+                }
+            }
+            if sender.blocking_send(batch).is_err() {
+                // The consumer dropped the stream; stop generating.
+                break;
+            }
+        }
+    });
+
+    futures::stream::unfold((receiver, 0), move |(mut receiver, produced)| async move {
+        if produced == number_coupons {
+            return None;
+        }
+        match receiver.recv().await {
+            Some(batch) => {
+                let produced = produced + batch.len();
+                let items: Vec<_> = batch.into_iter().map(Ok).collect();
+                Some((futures::stream::iter(items), (receiver, produced)))
+            }
+            None => {
+                let stopped = Err(CouponError::GenerationStopped(produced, number_coupons).into());
+                // Yield the error once, then end the stream
+                Some((futures::stream::iter(vec![stopped]), (receiver, number_coupons)))
+            }
+        }
+    })
+    .flatten()
+}
+
+/// A generator for [`spawn_producer`] when `number_coupons` is more than [`DENSE_FRACTION`] of
+/// the `space` possible coupons: it walks a random [`IndexPermutation`] of the space, so no draw
+/// is ever a repeat, `code` turning each index into its coupon. `None` when the request is not
+/// dense or the space does not fit in a u64 index.
+fn dense_generator<F>(
+    number_coupons: usize,
+    space: Option<u128>,
+    code: F,
+) -> Option<impl FnMut(&mut SmallRng) -> String + Send + 'static>
+where
+    F: Fn(u64) -> String + Send + 'static,
+{
+    // A space too large for a u64 index is left to rejection sampling
+    let space = space.filter(|&space| number_coupons as f64 > DENSE_FRACTION * space as f64)?;
+    let space = u64::try_from(space).ok()?;
+    let permutation = IndexPermutation::new(space, &mut SmallRng::from_entropy());
+    let mut next = 0;
+    Some(move |_: &mut SmallRng| {
+        next += 1;
+        code(permutation.permute(next - 1))
+    })
+}
+
+/// Generates a stream of unique coupons, the async counterpart of
+/// [`coupon_generator`](crate::coupons::coupon_generator).
+///
+/// Like it, requests for more than [`DENSE_FRACTION`] of all codes enumerate the space in a
+/// random order instead of rejecting repeats. At most about `buffer_size` coupons are generated
+/// ahead of the consumer; see [`spawn_producer`]. Must be called from within a Tokio runtime.
+///
+/// # Errors
+///
+/// Returns the errors of [`check_request`] before anything is generated.
+pub fn coupon_stream(
+    len: u16,
+    number_coupons: usize,
+    initials: &str,
+    charset: Charset,
+    buffer_size: usize,
+) -> Result<impl Stream<Item = Result<String, CouponError>>, CouponError> {
+    let code_len = check_request(len, number_coupons, initials, charset.len())?;
+    let initials = initials.to_string();
+    let space = max_combinations(charset.len(), code_len);
+    let generate: Box<dyn FnMut(&mut SmallRng) -> String + Send> = {
+        let (dense_initials, dense_charset) = (initials.clone(), charset.clone());
+        match dense_generator(number_coupons, space, move |index| {
+            code_from_index(index, code_len, &dense_initials, &dense_charset)
+        }) {
+            Some(dense) => Box::new(dense),
+            None => Box::new(move |rng| generate_coupon(rng, code_len, &initials, &charset)),
+        }
+    };
+    Ok(spawn_producer(number_coupons, buffer_size, generate))
+}
+
+/// Generates a stream of unique coupons laid out by `mask`, the async counterpart of
+/// [`coupon_generator_from_mask`](crate::coupons::coupon_generator_from_mask).
+///
+/// Must be called from within a Tokio runtime.
+///
+/// # Errors
+///
+/// Returns `CouponError::TooManyCoupons` if the mask allows fewer than `number_coupons` coupons.
+pub fn mask_stream(
+    mask: Mask,
+    number_coupons: usize,
+    buffer_size: usize,
+) -> Result<impl Stream<Item = Result<String, CouponError>>, CouponError> {
+    let space = mask.max_combinations();
+    if let Some(max_combinations) = space {
+        if number_coupons as u128 > max_combinations {
+            return Err(CouponError::TooManyCoupons(number_coupons, max_combinations));
+        }
+    }
+    let generate: Box<dyn FnMut(&mut SmallRng) -> String + Send> = {
+        let dense_mask = mask.clone();
+        match dense_generator(number_coupons, space, move |index| dense_mask.coupon_from_index(index)) {
+            Some(dense) => Box::new(dense),
+            None => Box::new(move |rng| generate_from_mask(rng, &mask)),
+        }
+    };
+    Ok(spawn_producer(number_coupons, buffer_size, generate))
+}
+
+/// Writes coupons to a CSV format.
+///
+/// This function takes a stream of coupons and writes them to the provided `AsyncWrite` in CSV format.
+///
+/// # Arguments
+///
+/// * `writer` - An `AsyncWrite` to which the CSV data will be written.
+/// * `coupons` - A `Stream` of `Result<String, E>` representing the coupons to be written.
+///
+/// # Returns
+///
+/// A `Result<(), E>` indicating success or failure of the operation.
+///
+/// # Errors
+///
+/// This function will return an error if there are issues writing to the CSV or if the input stream yields an error.
+pub async fn write_coupons_to_csv<W, E>(
+    writer: W,
+    coupons: impl Stream<Item = Result<String, E>>,
+) -> Result<(), E>
+where
+    W: AsyncWrite + Unpin + Send,
+    E: From<csv_async::Error> + From<std::io::Error>,
+{
+    let mut csv_writer = csv_async::AsyncWriter::from_writer(writer);
+    csv_writer.write_record(&["Coupon"]).await?;
+
+    tokio::pin!(coupons);
+    while let Some(coupon_result) = coupons.next().await {
+        let coupon = coupon_result?;
+        csv_writer.write_record(&[&coupon]).await?;
+    }
+
+    csv_writer.flush().await?;
+    Ok(())
+}
+
+/// Writes coupons in CSV format to a file, creating it or truncating an existing one.
+///
+/// This is [`write_coupons_to_csv`] on a `tokio::fs::File` opened at `path`.
+///
+/// # Errors
+///
+/// Returns `CouponError::FileCreationError` if the file cannot be created, and otherwise the
+/// errors of [`write_coupons_to_csv`].
+pub async fn write_coupons_to_csv_file<E>(
+    path: impl AsRef<Path>,
+    coupons: impl Stream<Item = Result<String, E>>,
+) -> Result<(), E>
+where
+    E: From<csv_async::Error> + From<std::io::Error>,
+{
+    let file = tokio::fs::File::create(path).await?;
+    write_coupons_to_csv(file, coupons).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::TryStreamExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn a_slow_consumer_bounds_the_coupons_in_flight() {
+        let buffer_size = 16;
+        let generated = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&generated);
+        let coupons = spawn_producer::<_, CouponError>(1_000, buffer_size, move |rng| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            generate_coupon(rng, 8, "", &Charset::default())
+        });
+        tokio::pin!(coupons);
+        let mut consumed = 0;
+        let mut most_in_flight = 0;
+        while let Some(coupon) = coupons.next().await {
+            coupon.unwrap();
+            consumed += 1;
+            if consumed % 50 == 0 {
+                tokio::time::sleep(Duration::from_millis(2)).await;
+            }
+            most_in_flight = most_in_flight.max(generated.load(Ordering::SeqCst) - consumed);
+        }
+        assert_eq!(consumed, 1_000);
+        // The channel, the batch being filled and the batch being handed out
+        assert!(most_in_flight <= 3 * buffer_size, "{} coupons were in flight", most_in_flight);
+    }
+
+    #[tokio::test]
+    async fn streams_exactly_the_requested_unique_coupons() {
+        let coupons: Vec<String> = coupon_stream(10, 20_000, "LISA", Charset::default(), 100)
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(coupons.len(), 20_000);
+        assert_eq!(coupons.iter().collect::<HashSet<_>>().len(), 20_000);
+        assert!(coupons.iter().all(|coupon| coupon.len() == 10 && coupon.starts_with("LISA")));
+    }
+
+    #[tokio::test]
+    async fn a_full_space_streams_every_code_once() {
+        let space = 36 * 36;
+        let coupons: Vec<String> = coupon_stream(4, space, "LI", Charset::default(), DEFAULT_BUFFER_SIZE)
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(coupons.iter().collect::<HashSet<_>>().len(), space);
+
+        let digits: Vec<String> = mask_stream(Mask::parse("X-##"), 100, DEFAULT_BUFFER_SIZE)
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(digits.iter().collect::<HashSet<_>>().len(), 100);
+        assert!(digits.iter().all(|coupon| coupon.starts_with("X-") && coupon[2..].chars().all(|c| c.is_ascii_digit())));
+        assert!(matches!(
+            mask_stream(Mask::parse("##"), 101, DEFAULT_BUFFER_SIZE).err(),
+            Some(CouponError::TooManyCoupons(101, 100))
+        ));
+    }
+
+    #[tokio::test]
+    async fn writes_a_stream_to_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("coupons.csv");
+        let coupons = coupon_stream(10, 25, "LISA", Charset::default(), DEFAULT_BUFFER_SIZE).unwrap();
+        write_coupons_to_csv_file::<CouponError>(&path, coupons).await.unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let mut lines = content.lines();
+        assert_eq!(lines.next(), Some("Coupon"));
+        let rows: Vec<&str> = lines.collect();
+        assert_eq!(rows.len(), 25);
+        assert!(rows.iter().all(|row| row.len() == 10 && row.starts_with("LISA")));
+    }
+}
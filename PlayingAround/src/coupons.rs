@@ -0,0 +1,1915 @@
+//! Generation of unique coupon codes and writing them as CSV, shared by the coupon binaries.
+//!
+//! A coupon is a fixed prefix (the initials) followed by a random code drawn from a [`Charset`],
+//! [`CHARSET`] unless the caller picks another one.
+
+use rand::distributions::WeightedIndex;
+use rand::prelude::*; // Import random number generation traits and functions
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use csv::Writer;
+use chrono::{Days, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+/// Custom error type for the coupon generation process.
+#[derive(Error, Debug)]
+pub enum CouponError {
+    /// Error when the initials length exceeds the total coupon length.
+    #[error("Initials length ({0}) cannot be greater than the total coupon length ({1})")]
+    InitialsTooLong(usize, u16),
+
+    /// Error when the requested number of coupons exceeds the maximum possible combinations.
+    #[error(
+        "Cannot generate {0} unique coupons with the given length and character set. Maximum possible is {1}"
+    )]
+    TooManyCoupons(usize, u128),
+
+    /// Error when writing to the CSV file fails.
+    #[error("Failed to write to CSV file: {0}")]
+    CsvWriteError(#[from] csv::Error),
+
+    /// Error when creating the output file fails.
+    #[error("Failed to create file: {0}")]
+    FileCreationError(#[from] std::io::Error),
+
+    /// Error when a charset has no characters to draw from.
+    #[error("The character set is empty")]
+    EmptyCharset,
+
+    /// Error when a charset contains a character that is not printable ASCII.
+    #[error("The character set can only hold printable ASCII characters, got {0:?}")]
+    InvalidCharsetCharacter(char),
+
+    /// Error when a check character is requested for a coupon with a character outside the charset.
+    #[error("Cannot compute a check character over {0:?}, it is not in the character set")]
+    CharacterOutsideCharset(char),
+
+    /// Error when a coupon would expire after the last date that can be represented.
+    #[error("Coupons valid for {0} days would expire after the last representable date")]
+    ExpiryOutOfRange(u32),
+
+    /// Error when writing CSV from async code fails.
+    #[error("Failed to write CSV data: {0}")]
+    AsyncCsvWriteError(#[from] csv_async::Error),
+
+    /// Error when the task generating a coupon stream stops before it produced every coupon,
+    /// e.g. by panicking.
+    #[error("Coupon generation stopped after {0} of {1} coupons")]
+    GenerationStopped(usize, usize),
+
+    /// Error when a checkpoint file belongs to a run with different parameters.
+    #[error("Checkpoint {0} was written for different parameters, expected \"{1}\"")]
+    CheckpointMismatch(String, String),
+
+    /// Error when a coupon does not have the expected format, e.g. when grouping it.
+    #[error("Invalid coupon: {0}")]
+    InvalidFormat(#[from] FormatError),
+
+    /// Error when a weighted charset is empty or has a weight that is not a positive number.
+    #[error("Invalid charset weights: {0}")]
+    InvalidWeights(String),
+
+    /// Error when reading a coupon CSV file fails.
+    #[error("Failed to read CSV data: {0}")]
+    CsvReadError(csv::Error),
+
+    /// Error when a manifest cannot be written or parsed.
+    #[error("Manifest error: {0}")]
+    ManifestError(#[from] serde_json::Error),
+}
+
+/// The default character set used for generating the coupon codes.
+pub const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// The length of the character set.
+pub const CHARSET_LEN: usize = CHARSET.len();
+
+/// The number of coupons [`coupon_generator_to_csv`] lets wait for the CSV writer.
+const CSV_CHANNEL_CAPACITY: usize = 10_000;
+
+/// The default number of new coupons generated between two checkpoint flushes.
+pub const DEFAULT_CHECKPOINT_INTERVAL: usize = 100_000;
+
+/// Crockford's base32 alphabet: the digits and the letters without I, L, O and U, so a code
+/// read aloud or typed by hand has no look-alike characters.
+pub const CROCKFORD_CHARSET: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// The characters the random part of a coupon is drawn from, each equally likely unless the
+/// charset was made with [`Charset::weighted`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Charset {
+    chars: Vec<u8>,
+    /// The relative weight of each character, in the order of `chars`, and the distribution
+    /// sampling them; `None` for a uniform charset.
+    weights: Option<(Vec<f64>, WeightedIndex<f64>)>,
+}
+
+impl Charset {
+    /// Creates a charset from the given characters; repeated characters are kept once, so
+    /// they do not become more likely than the others.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CouponError::EmptyCharset` if `chars` is empty, and
+    /// `CouponError::InvalidCharsetCharacter` if one is not printable ASCII.
+    pub fn new(chars: &[u8]) -> Result<Self, CouponError> {
+        if let Some(&c) = chars.iter().find(|c| !c.is_ascii_graphic()) {
+            return Err(CouponError::InvalidCharsetCharacter(c as char));
+        }
+        let mut seen = HashSet::new();
+        let chars: Vec<u8> = chars.iter().copied().filter(|c| seen.insert(*c)).collect();
+        if chars.is_empty() {
+            return Err(CouponError::EmptyCharset);
+        }
+        Ok(Charset { chars, weights: None })
+    }
+
+    /// Creates a charset where each character is drawn with a probability proportional to its
+    /// weight, from `(character, relative weight)` pairs.
+    ///
+    /// The feasibility check counts every character, whatever its weight. Requests dense enough
+    /// to be enumerated (see [`DENSE_FRACTION`]) give every code once, so the weights only shape
+    /// the coupons drawn at random.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CouponError::InvalidWeights` if `weights` is empty, a character is given twice
+    /// or a weight is not a finite number greater than zero, and
+    /// `CouponError::InvalidCharsetCharacter` if a character is not printable ASCII.
+    pub fn weighted(weights: &[(u8, f64)]) -> Result<Self, CouponError> {
+        if weights.is_empty() {
+            return Err(CouponError::InvalidWeights("the charset is empty".to_string()));
+        }
+        if let Some(&(c, w)) = weights.iter().find(|(_, w)| !(w.is_finite() && *w > 0.0)) {
+            return Err(CouponError::InvalidWeights(format!(
+                "weight for '{}' must be positive, got {}",
+                c as char, w
+            )));
+        }
+        let chars: Vec<u8> = weights.iter().map(|&(c, _)| c).collect();
+        let charset = Charset::new(&chars)?;
+        if charset.len() < chars.len() {
+            return Err(CouponError::InvalidWeights("a character is given more than once".to_string()));
+        }
+        let weights: Vec<f64> = weights.iter().map(|&(_, w)| w).collect();
+        let distribution =
+            WeightedIndex::new(&weights).map_err(|e| CouponError::InvalidWeights(e.to_string()))?;
+        Ok(Charset { weights: Some((weights, distribution)), ..charset })
+    }
+
+    /// Uppercase letters and digits: [`CHARSET`].
+    pub fn alphanumeric() -> Self {
+        Charset { chars: CHARSET.to_vec(), weights: None }
+    }
+
+    /// Uppercase and lowercase letters and digits.
+    pub fn mixed_case() -> Self {
+        Charset {
+            chars: b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789".to_vec(),
+            weights: None,
+        }
+    }
+
+    /// Uppercase letters only.
+    pub fn uppercase() -> Self {
+        Charset { chars: b"ABCDEFGHIJKLMNOPQRSTUVWXYZ".to_vec(), weights: None }
+    }
+
+    /// Digits only.
+    pub fn digits() -> Self {
+        Charset { chars: b"0123456789".to_vec(), weights: None }
+    }
+
+    /// The [`CROCKFORD_CHARSET`]; see [`matches_crockford`] for checking codes typed by users.
+    pub fn crockford() -> Self {
+        Charset { chars: CROCKFORD_CHARSET.to_vec(), weights: None }
+    }
+
+    /// The characters of the charset, in the order they were given.
+    pub fn chars(&self) -> &[u8] {
+        &self.chars
+    }
+
+    /// The number of distinct characters; never zero.
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    /// Always `false`: a charset holds at least one character.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// The position of `c` in the charset, or `None` if it is not one of its characters.
+    pub fn index_of(&self, c: char) -> Option<usize> {
+        self.chars.iter().position(|&b| b as char == c)
+    }
+
+    /// A character drawn uniformly, or according to the weights of a weighted charset.
+    pub fn sample(&self, rng: &mut impl Rng) -> char {
+        let index = match &self.weights {
+            Some((_, distribution)) => distribution.sample(rng),
+            None => rng.gen_range(0..self.chars.len()),
+        };
+        self.chars[index] as char
+    }
+}
+
+/// Characters that are easily mistaken for one another on paper: 0/O, 1/I/l, 5/S and 8/B.
+pub const AMBIGUOUS_CHARS: &[u8] = b"0O1Il5S8B";
+
+/// Adjustments made to a [`Charset`] before generating coupons.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CharsetOptions {
+    /// Leave out the [`AMBIGUOUS_CHARS`].
+    pub exclude_ambiguous: bool,
+}
+
+impl Charset {
+    /// The charset adjusted by `options`; the characters left keep their weights.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CouponError::EmptyCharset` if no character is left.
+    pub fn with_options(&self, options: CharsetOptions) -> Result<Self, CouponError> {
+        let kept = |c: &u8| !(options.exclude_ambiguous && AMBIGUOUS_CHARS.contains(c));
+        match &self.weights {
+            Some((weights, _)) => {
+                let weights: Vec<(u8, f64)> =
+                    self.chars.iter().copied().zip(weights.iter().copied()).filter(|(c, _)| kept(c)).collect();
+                if weights.is_empty() {
+                    return Err(CouponError::EmptyCharset);
+                }
+                Charset::weighted(&weights)
+            }
+            None => Charset::new(&self.chars.iter().copied().filter(kept).collect::<Vec<u8>>()),
+        }
+    }
+}
+
+impl Default for Charset {
+    fn default() -> Self {
+        Charset::alphanumeric()
+    }
+}
+
+/// Parses a preset name (`alphanumeric`, `mixed-case`, `uppercase`, `digits` or `crockford`) or
+/// else takes the text as the characters themselves, e.g. `ABC123`.
+impl std::str::FromStr for Charset {
+    type Err = CouponError;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "alphanumeric" => Ok(Charset::alphanumeric()),
+            "mixed-case" => Ok(Charset::mixed_case()),
+            "uppercase" => Ok(Charset::uppercase()),
+            "digits" => Ok(Charset::digits()),
+            "crockford" => Ok(Charset::crockford()),
+            _ => match text.chars().find(|c| !c.is_ascii_graphic()) {
+                // Report the whole character rather than its first UTF-8 byte
+                Some(c) => Err(CouponError::InvalidCharsetCharacter(c)),
+                None => Charset::new(text.as_bytes()),
+            },
+        }
+    }
+}
+
+/// Generates a single coupon code with the specified code length and initials.
+///
+/// # Arguments
+///
+/// * `rng` - A mutable reference to a random number generator.
+/// * `code_len` - The length of the random part of the coupon code.
+/// * `initials` - The initials to prefix the coupon code with.
+/// * `charset` - The characters the random part is drawn from.
+///
+/// # Returns
+///
+/// A `String` representing the generated coupon code.
+pub fn generate_coupon(rng: &mut SmallRng, code_len: usize, initials: &str, charset: &Charset) -> String {
+    // Pre-allocate a string with the required capacity for efficiency
+    let mut coupon = String::with_capacity(initials.len() + code_len);
+    coupon.push_str(initials); // Add the initials to the coupon code
+
+    // Draw each character with `Charset::sample`; reducing a random byte modulo the charset
+    // length would favour the first 256 % len characters
+    for _ in 0..code_len {
+        coupon.push(charset.sample(rng));
+    }
+
+    coupon
+}
+
+/// Checks the requested coupons fit in the space left by the initials, when each character of
+/// the random part is one of `charset_len` characters.
+///
+/// # Returns
+///
+/// The length of the random part of each coupon code, or a `CouponError`.
+pub fn check_request(
+    len: u16,
+    number_coupons: usize,
+    initials: &str,
+    charset_len: usize,
+) -> Result<usize, CouponError> {
+    let initial_len = initials.len();
+
+    // Check if the initials length exceeds the total coupon length
+    if initial_len > len as usize {
+        return Err(CouponError::InitialsTooLong(initial_len, len));
+    }
+    let code_len = len as usize - initial_len;
+
+    // Calculate the maximum possible combinations based on the charset and code length; a
+    // space too large for a u128 holds any number of coupons
+    if let Some(max_combinations) = max_combinations(charset_len, code_len) {
+        if number_coupons as u128 > max_combinations {
+            return Err(CouponError::TooManyCoupons(number_coupons, max_combinations));
+        }
+    }
+    Ok(code_len)
+}
+
+/// The number of distinct random parts of `code_len` characters from a charset of
+/// `charset_len`, or `None` when it does not fit in a `u128`.
+pub fn max_combinations(charset_len: usize, code_len: usize) -> Option<u128> {
+    u32::try_from(code_len).ok().and_then(|exp| (charset_len as u128).checked_pow(exp))
+}
+
+/// The fraction of the coupon space above which [`coupon_generator`] enumerates the space
+/// instead of drawing random coupons and rejecting the repeats.
+pub const DENSE_FRACTION: f64 = 0.5;
+
+/// Rounds of the Feistel network behind [`IndexPermutation`].
+const FEISTEL_ROUNDS: usize = 4;
+
+/// A random permutation of `0..domain`, evaluated one index at a time without storing it.
+///
+/// A balanced Feistel network permutes the smallest even bit width covering `domain`; indices
+/// it maps outside `domain` are fed through again ("cycle walking") until they land inside.
+/// The width is at most four times `domain`, so each index takes a few rounds on average.
+#[derive(Clone, Debug)]
+pub struct IndexPermutation {
+    domain: u64,
+    half_bits: u32,
+    keys: [u64; FEISTEL_ROUNDS],
+}
+
+impl IndexPermutation {
+    /// Draws the round keys of a permutation of `0..domain` from `rng`.
+    pub fn new(domain: u64, rng: &mut impl Rng) -> Self {
+        let bits = (u64::BITS - domain.saturating_sub(1).leading_zeros()).max(2);
+        IndexPermutation { domain, half_bits: bits.div_ceil(2), keys: rng.gen() }
+    }
+
+    /// The image of `index`, which must be below the domain.
+    pub fn permute(&self, mut index: u64) -> u64 {
+        debug_assert!(index < self.domain);
+        let mask = (1 << self.half_bits) - 1;
+        loop {
+            let (mut left, mut right) = (index >> self.half_bits, index & mask);
+            for key in self.keys {
+                (left, right) = (right, left ^ (mix(right ^ key) & mask));
+            }
+            index = (left << self.half_bits) | right;
+            if index < self.domain {
+                return index;
+            }
+        }
+    }
+}
+
+/// The SplitMix64 finalizer, the round function of [`IndexPermutation`] and what [`sub_seed`]
+/// mixes seeds with.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// The coupon numbered `index` among all `charset.len().pow(code_len)` codes, written in base
+/// `charset.len()` with one charset character per digit.
+pub fn code_from_index(mut index: u64, code_len: usize, initials: &str, charset: &Charset) -> String {
+    let chars = charset.chars();
+    let base = chars.len() as u64;
+    let mut code = vec![chars[0]; code_len];
+    for position in code.iter_mut().rev() {
+        *position = chars[(index % base) as usize];
+        index /= base;
+    }
+    let mut coupon = String::with_capacity(initials.len() + code_len);
+    coupon.push_str(initials);
+    coupon.extend(code.into_iter().map(char::from));
+    coupon
+}
+
+/// Generates `number_coupons` distinct coupons in linear time by taking the images of
+/// `0..number_coupons` under a random [`IndexPermutation`] of the whole coupon space, `code`
+/// turning each index into its coupon.
+fn enumerate_dense<F>(number_coupons: usize, max_combinations: u64, seed: Option<u64>, code: F) -> Vec<String>
+where
+    F: Fn(u64) -> String + Sync,
+{
+    let mut rng = match seed {
+        Some(seed) => seeded_rng(seed, 0),
+        None => SmallRng::from_entropy(),
+    };
+    let permutation = IndexPermutation::new(max_combinations, &mut rng);
+    (0..number_coupons as u64)
+        .into_par_iter()
+        .map(|index| code(permutation.permute(index)))
+        .collect()
+}
+
+/// Generates `additional` coupons that are not in `coupons` yet, in parallel.
+///
+/// # Arguments
+///
+/// * `coupons` - The set of coupons generated so far; the new ones are added to it.
+/// * `additional` - The number of new unique coupons to generate.
+/// * `seed` - Makes the new coupons a function of the seed and `coupons`; see [`extend_seeded`].
+/// * `generate` - Draws one coupon, e.g. with [`generate_coupon`].
+///
+/// # Returns
+///
+/// The newly generated coupons.
+fn extend_unique<G>(
+    coupons: &Arc<parking_lot::Mutex<HashSet<String>>>,
+    additional: usize,
+    seed: Option<u64>,
+    generate: &G,
+) -> Vec<String>
+where
+    G: Fn(&mut SmallRng) -> String + Sync,
+{
+    if let Some(seed) = seed {
+        return extend_seeded(&mut coupons.lock(), additional, seed, generate);
+    }
+    let counter = Arc::new(AtomicUsize::new(0));
+    let generated = Arc::new(parking_lot::Mutex::new(Vec::with_capacity(additional)));
+
+    // Use Rayon for parallel execution
+    rayon::scope(|s| {
+        for _ in 0..rayon::current_num_threads() {
+            let coupons = Arc::clone(coupons);
+            let counter = Arc::clone(&counter);
+            let generated = Arc::clone(&generated);
+            s.spawn(move |_| {
+                let mut rng = SmallRng::from_entropy();
+                let mut mine = Vec::new();
+                loop {
+                    // Atomically get the next number to process
+                    let my_number = counter.fetch_add(1, Ordering::SeqCst);
+                    if my_number >= additional {
+                        break;
+                    }
+
+                    // Generate unique coupons
+                    loop {
+                        let coupon = generate(&mut rng);
+                        let mut set = coupons.lock();
+                        if set.insert(coupon.clone()) {
+                            mine.push(coupon);
+                            break; // Break if the coupon is unique
+                        }
+                    }
+                }
+                generated.lock().append(&mut mine);
+            });
+        }
+    });
+
+    Arc::try_unwrap(generated).unwrap().into_inner()
+}
+
+/// The number of random streams a seeded run is split over. It is fixed instead of following
+/// the thread count, so a seed gives the same coupons on every machine.
+const SEEDED_WORKERS: usize = 8;
+
+/// The seed of stream `stream` of a run seeded with `seed`. Seed and stream are mixed together
+/// rather than added, so stream 1 of seed 42 is not stream 0 of seed 43.
+fn sub_seed(seed: u64, stream: u64) -> u64 {
+    mix(seed ^ stream.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+}
+
+/// The random number generator of stream `stream` of a run seeded with `seed`.
+fn seeded_rng(seed: u64, stream: u64) -> SmallRng {
+    SmallRng::seed_from_u64(sub_seed(seed, stream))
+}
+
+/// Generates `additional` coupons that are not in `coupons` yet, the same ones for the same
+/// `seed` and `coupons`.
+///
+/// Each of the [`SEEDED_WORKERS`] workers fills its own share from its own stream. Two workers
+/// can still draw the same coupon, so the shares are merged in worker order and those repeats
+/// redrawn from one more stream, leaving nothing to the thread scheduling.
+fn extend_seeded<G>(coupons: &mut HashSet<String>, additional: usize, seed: u64, generate: &G) -> Vec<String>
+where
+    G: Fn(&mut SmallRng) -> String + Sync,
+{
+    let existing = &*coupons;
+    let shares: Vec<Vec<String>> = (0..SEEDED_WORKERS)
+        .into_par_iter()
+        .map(|worker| {
+            let share = additional / SEEDED_WORKERS + usize::from(worker < additional % SEEDED_WORKERS);
+            let mut rng = seeded_rng(seed, worker as u64);
+            let mut mine = HashSet::with_capacity(share);
+            let mut generated = Vec::with_capacity(share);
+            while generated.len() < share {
+                let coupon = generate(&mut rng);
+                if !existing.contains(&coupon) && mine.insert(coupon.clone()) {
+                    generated.push(coupon);
+                }
+            }
+            generated
+        })
+        .collect();
+
+    let mut rng = seeded_rng(seed, SEEDED_WORKERS as u64);
+    let mut generated = Vec::with_capacity(additional);
+    for mut coupon in shares.into_iter().flatten() {
+        while !coupons.insert(coupon.clone()) {
+            coupon = generate(&mut rng);
+        }
+        generated.push(coupon);
+    }
+    generated
+}
+
+/// Generates a specified number of unique coupon codes.
+///
+/// Coupons are drawn at random and repeats rejected, unless more than [`DENSE_FRACTION`] of all
+/// possible codes is requested; those are enumerated in a random order, see [`IndexPermutation`].
+///
+/// # Arguments
+///
+/// * `len` - The total length of each coupon code (initials + random code).
+/// * `number_coupons` - The total number of unique coupons to generate.
+/// * `initials` - The initials to prefix each coupon code with.
+/// * `charset` - The characters the random part of each code is drawn from.
+/// * `seed` - When given, the same seed always generates the same coupons, in the same order;
+///   otherwise the generator is seeded from the operating system.
+///
+/// # Returns
+///
+/// A `Result` containing a vector of unique coupon codes or a `CouponError`.
+pub fn coupon_generator(
+    len: u16,
+    number_coupons: usize,
+    initials: &str,
+    charset: &Charset,
+    seed: Option<u64>,
+) -> Result<Vec<String>, CouponError> {
+    let code_len = check_request(len, number_coupons, initials, charset.len())?;
+
+    // Near capacity almost every random draw is a repeat, so walk a permutation of the space
+    // instead; a space too large for a u64 index falls back to rejecting repeats
+    if let Some(max_combinations) = max_combinations(charset.len(), code_len) {
+        if number_coupons as f64 > DENSE_FRACTION * max_combinations as f64 {
+            if let Ok(max_combinations) = u64::try_from(max_combinations) {
+                let code = |index| code_from_index(index, code_len, initials, charset);
+                return Ok(enumerate_dense(number_coupons, max_combinations, seed, code));
+            }
+        }
+    }
+
+    // Use a thread-safe set to store unique coupons
+    let coupons = Arc::new(parking_lot::Mutex::new(HashSet::with_capacity(number_coupons)));
+    let generate = |rng: &mut SmallRng| generate_coupon(rng, code_len, initials, charset);
+    Ok(extend_unique(&coupons, number_coupons, seed, &generate))
+}
+
+/// The first line of a checkpoint file, identifying the run it belongs to.
+fn checkpoint_header(len: u16, number_coupons: usize, initials: &str, charset: &Charset) -> String {
+    let charset = String::from_utf8_lossy(charset.chars());
+    format!("# len={len} count={number_coupons} initials={initials} charset={charset}")
+}
+
+/// Reads the coupons saved in a checkpoint file, or none if the file does not exist yet.
+///
+/// A line that is not a complete coupon (e.g. cut off by a crash mid-write) is dropped,
+/// as are duplicates.
+fn load_checkpoint(
+    checkpoint: &Path,
+    header: &str,
+    len: u16,
+    initials: &str,
+) -> Result<Vec<String>, CouponError> {
+    let content = match fs::read_to_string(checkpoint) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut lines = content.lines();
+    if lines.next() != Some(header) {
+        return Err(CouponError::CheckpointMismatch(
+            checkpoint.display().to_string(),
+            header.to_string(),
+        ));
+    }
+    let mut seen = HashSet::new();
+    Ok(lines
+        .filter(|line| line.len() == len as usize && line.starts_with(initials))
+        .filter(|line| seen.insert(*line))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Generates unique coupon codes, saving progress to a checkpoint file along the way.
+///
+/// Every `interval` new coupons are appended to `checkpoint` and flushed to disk. When the
+/// checkpoint already exists and was written with the same parameters, its coupons are
+/// reloaded and generation continues until `number_coupons` are reached.
+///
+/// # Arguments
+///
+/// * `len` - The total length of each coupon code (initials + random code).
+/// * `number_coupons` - The total number of unique coupons to generate.
+/// * `initials` - The initials to prefix each coupon code with.
+/// * `charset` - The characters the random part of each code is drawn from.
+/// * `checkpoint` - The file keeping the coupons generated so far.
+/// * `interval` - The number of new coupons generated between two flushes.
+/// * `seed` - When given, the coupons depend only on the seed and on the checkpoint being
+///   resumed; otherwise the generator is seeded from the operating system.
+///
+/// # Returns
+///
+/// A `Result` containing a vector of unique coupon codes or a `CouponError`.
+pub fn coupon_generator_resumable(
+    len: u16,
+    number_coupons: usize,
+    initials: &str,
+    charset: &Charset,
+    checkpoint: &Path,
+    interval: usize,
+    seed: Option<u64>,
+) -> Result<Vec<String>, CouponError> {
+    let code_len = check_request(len, number_coupons, initials, charset.len())?;
+    let header = checkpoint_header(len, number_coupons, initials, charset);
+    let mut all_coupons = load_checkpoint(checkpoint, &header, len, initials)?;
+    all_coupons.truncate(number_coupons);
+
+    // Rewrite the checkpoint so a partial last line never gets new coupons appended to it
+    let mut writer = BufWriter::new(File::create(checkpoint)?);
+    writeln!(writer, "{header}")?;
+    for coupon in &all_coupons {
+        writeln!(writer, "{coupon}")?;
+    }
+    writer.flush()?;
+    drop(writer);
+
+    let mut writer = BufWriter::new(OpenOptions::new().append(true).open(checkpoint)?);
+    let mut set = HashSet::with_capacity(number_coupons);
+    set.extend(all_coupons.iter().cloned());
+    let coupons = Arc::new(parking_lot::Mutex::new(set));
+    let generate = |rng: &mut SmallRng| generate_coupon(rng, code_len, initials, charset);
+
+    while all_coupons.len() < number_coupons {
+        let batch = interval.max(1).min(number_coupons - all_coupons.len());
+        // Every batch gets a seed of its own, derived from how many coupons came before it
+        let batch_seed = seed.map(|seed| sub_seed(seed, all_coupons.len() as u64));
+        let new_coupons = extend_unique(&coupons, batch, batch_seed, &generate);
+        for coupon in &new_coupons {
+            writeln!(writer, "{coupon}")?;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_data()?;
+        all_coupons.extend(new_coupons);
+    }
+    Ok(all_coupons)
+}
+
+/// The characters a `#` or `{D}` of a [`Mask`] is replaced with.
+const MASK_DIGITS: &[u8] = b"0123456789";
+
+/// The characters an `A` or `{L}` of a [`Mask`] is replaced with.
+const MASK_LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// One position of a [`Mask`].
+#[derive(Clone, Debug, PartialEq)]
+enum MaskPosition {
+    /// A character drawn uniformly from these.
+    Random(&'static [u8]),
+    /// A character copied as-is.
+    Literal(char),
+}
+
+/// The layout of a coupon such as `"LISA-####-AAAA"`: every `#` becomes a random digit, every
+/// `A` a random uppercase letter, and every other character is copied as-is.
+///
+/// Only a run of letters made of nothing but `A`s is random, so the initials in `"LISA-"` stay
+/// as written while `"AAAA"` becomes four letters. A backslash makes the character after it a
+/// literal, e.g. `"\\A-####"` for coupons starting with "A-".
+///
+/// The classes `{L}` (a letter), `{D}` (a digit) and `{A}` (any character of [`CHARSET`]) can be
+/// used anywhere, e.g. `"{L}{L}{D}{D}{D}{L}"` for two letters, three digits and one letter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mask {
+    positions: Vec<MaskPosition>,
+}
+
+impl Mask {
+    /// Parses a mask; it cannot fail, since every unknown character is a literal.
+    pub fn parse(mask: &str) -> Self {
+        let chars: Vec<char> = mask.chars().collect();
+        let mut positions = Vec::with_capacity(chars.len());
+        let mut i = 0;
+        while i < chars.len() {
+            let class = match chars.get(i..i + 3) {
+                Some(['{', 'L', '}']) => Some(MASK_LETTERS),
+                Some(['{', 'D', '}']) => Some(MASK_DIGITS),
+                Some(['{', 'A', '}']) => Some(CHARSET),
+                _ => None,
+            };
+            if let Some(class) = class {
+                positions.push(MaskPosition::Random(class));
+                i += 3;
+                continue;
+            }
+            match chars[i] {
+                '#' => positions.push(MaskPosition::Random(MASK_DIGITS)),
+                // A trailing backslash has nothing to escape and stands for itself
+                '\\' => {
+                    i += 1;
+                    positions.push(MaskPosition::Literal(chars.get(i).copied().unwrap_or('\\')));
+                }
+                c if c.is_ascii_alphabetic() => {
+                    let run = chars[i..].iter().take_while(|c| c.is_ascii_alphabetic()).count();
+                    let word = &chars[i..i + run];
+                    if word.iter().all(|&c| c == 'A') {
+                        positions.extend(word.iter().map(|_| MaskPosition::Random(MASK_LETTERS)));
+                    } else {
+                        positions.extend(word.iter().map(|&c| MaskPosition::Literal(c)));
+                    }
+                    i += run;
+                    continue;
+                }
+                c => positions.push(MaskPosition::Literal(c)),
+            }
+            i += 1;
+        }
+        Mask { positions }
+    }
+
+    /// The number of distinct coupons, counting only the random positions, or `None` when it
+    /// does not fit in a `u128`.
+    pub fn max_combinations(&self) -> Option<u128> {
+        self.positions.iter().try_fold(1u128, |total, position| match position {
+            MaskPosition::Random(chars) => total.checked_mul(chars.len() as u128),
+            MaskPosition::Literal(_) => Some(total),
+        })
+    }
+
+    /// The coupon numbered `index` among all [`Mask::max_combinations`], the last random
+    /// position changing fastest.
+    pub fn coupon_from_index(&self, mut index: u64) -> String {
+        let mut coupon: Vec<char> = self
+            .positions
+            .iter()
+            .rev()
+            .map(|position| match position {
+                MaskPosition::Random(chars) => {
+                    let c = chars[(index % chars.len() as u64) as usize] as char;
+                    index /= chars.len() as u64;
+                    c
+                }
+                MaskPosition::Literal(c) => *c,
+            })
+            .collect();
+        coupon.reverse();
+        coupon.into_iter().collect()
+    }
+}
+
+impl std::str::FromStr for Mask {
+    type Err = std::convert::Infallible;
+
+    fn from_str(mask: &str) -> Result<Self, Self::Err> {
+        Ok(Mask::parse(mask))
+    }
+}
+
+/// Generates a single coupon laid out by `mask`.
+pub fn generate_from_mask(rng: &mut SmallRng, mask: &Mask) -> String {
+    mask.positions
+        .iter()
+        .map(|position| match position {
+            MaskPosition::Random(chars) => chars[rng.gen_range(0..chars.len())] as char,
+            MaskPosition::Literal(c) => *c,
+        })
+        .collect()
+}
+
+/// Generates unique coupons laid out by `mask`, like [`coupon_generator`] does for initials
+/// followed by a random part.
+///
+/// # Errors
+///
+/// Returns `CouponError::TooManyCoupons` if the mask allows fewer than `number_coupons` coupons.
+pub fn coupon_generator_from_mask(
+    mask: &Mask,
+    number_coupons: usize,
+    seed: Option<u64>,
+) -> Result<Vec<String>, CouponError> {
+    if let Some(max_combinations) = mask.max_combinations() {
+        if number_coupons as u128 > max_combinations {
+            return Err(CouponError::TooManyCoupons(number_coupons, max_combinations));
+        }
+        if number_coupons as f64 > DENSE_FRACTION * max_combinations as f64 {
+            if let Ok(max_combinations) = u64::try_from(max_combinations) {
+                let code = |index| mask.coupon_from_index(index);
+                return Ok(enumerate_dense(number_coupons, max_combinations, seed, code));
+            }
+        }
+    }
+    let coupons = Arc::new(parking_lot::Mutex::new(HashSet::with_capacity(number_coupons)));
+    let generate = |rng: &mut SmallRng| generate_from_mask(rng, mask);
+    Ok(extend_unique(&coupons, number_coupons, seed, &generate))
+}
+
+/// The Luhn mod N check character of `payload`, N being the size of `charset`.
+///
+/// With [`Charset::digits`] this is the classic Luhn check digit. Any single mistyped character
+/// and most swaps of two neighbours change the check character.
+///
+/// # Errors
+///
+/// Returns `CouponError::CharacterOutsideCharset` if `payload` has a character not in `charset`.
+pub fn check_character(payload: &str, charset: &Charset) -> Result<char, CouponError> {
+    let n = charset.len();
+    let sum = luhn_sum(payload, charset, 2)?;
+    Ok(charset.chars()[(n - sum % n) % n] as char)
+}
+
+/// The Luhn sum of `text` with the rightmost character weighted by `first_factor` and the
+/// weights alternating between 2 and 1 to the left.
+fn luhn_sum(text: &str, charset: &Charset, first_factor: usize) -> Result<usize, CouponError> {
+    let n = charset.len();
+    let mut factor = first_factor;
+    let mut sum = 0;
+    for c in text.chars().rev() {
+        let index = charset.index_of(c).ok_or(CouponError::CharacterOutsideCharset(c))?;
+        let addend = factor * index;
+        sum += addend / n + addend % n;
+        factor = 3 - factor;
+    }
+    Ok(sum)
+}
+
+/// Whether the last character of `coupon` is the [`check_character`] of the rest over `charset`.
+pub fn validate_coupon_with(coupon: &str, charset: &Charset) -> bool {
+    !coupon.is_empty() && luhn_sum(coupon, charset, 1).is_ok_and(|sum| sum % charset.len() == 0)
+}
+
+/// Whether the last character of `coupon` is the check character of the rest, over [`CHARSET`].
+pub fn validate_coupon(coupon: &str) -> bool {
+    validate_coupon_with(coupon, &Charset::alphanumeric())
+}
+
+/// Like [`coupon_generator`], except that the last of the `len` characters of each coupon is the
+/// [`check_character`] of the initials and random part before it.
+///
+/// # Errors
+///
+/// Besides the errors of [`coupon_generator`], returns `CouponError::CharacterOutsideCharset`
+/// if the initials hold a character outside of `charset`.
+pub fn coupon_generator_with_check(
+    len: u16,
+    number_coupons: usize,
+    initials: &str,
+    charset: &Charset,
+    seed: Option<u64>,
+) -> Result<Vec<String>, CouponError> {
+    let payload_len = len.checked_sub(1).ok_or(CouponError::InitialsTooLong(initials.len(), len))?;
+    // Fail before generating anything when the check cannot be computed
+    check_character(initials, charset)?;
+    coupon_generator(payload_len, number_coupons, initials, charset, seed)?
+        .into_iter()
+        .map(|coupon| append_check_character(coupon, charset))
+        .collect()
+}
+
+/// Generates unique coupons and writes them as CSV while they are generated, for batches too
+/// large to hold twice in memory.
+///
+/// The workers of [`coupon_generator`] send each new coupon over a channel to one thread
+/// writing the CSV, so only the set used to reject repeats stays in memory. The rows come out
+/// in the order the workers found them.
+///
+/// With `valid_days` the CSV gets an `ExpiresAt` column; see [`write_coupons_to_csv`].
+///
+/// # Errors
+///
+/// Returns the errors of [`check_request`] before writing anything, and
+/// `CouponError::CsvWriteError` if writing fails, which also stops the workers.
+pub fn coupon_generator_to_csv<W: Write + Send>(
+    len: u16,
+    number_coupons: usize,
+    initials: &str,
+    charset: &Charset,
+    writer: W,
+    valid_days: Option<u32>,
+) -> Result<(), CouponError> {
+    let code_len = check_request(len, number_coupons, initials, charset.len())?;
+    let writer = CouponCsv::new(writer, valid_days)?;
+    stream_unique(code_len, number_coupons, initials, charset, writer)?.finish()?;
+    Ok(())
+}
+
+/// Like [`coupon_generator_to_csv`], except that the coupons are split over files of at most
+/// `rows_per_file` rows each; see [`write_coupons_to_files`] for how they are named.
+///
+/// # Returns
+///
+/// The paths of the files written, in order.
+pub fn coupon_generator_to_files(
+    len: u16,
+    number_coupons: usize,
+    initials: &str,
+    charset: &Charset,
+    path: &Path,
+    rows_per_file: usize,
+    valid_days: Option<u32>,
+) -> Result<Vec<PathBuf>, CouponError> {
+    let code_len = check_request(len, number_coupons, initials, charset.len())?;
+    let files = CouponFiles::new(path, rows_per_file, valid_days)?;
+    stream_unique(code_len, number_coupons, initials, charset, files)?.finish()
+}
+
+/// Where [`stream_unique`] hands the coupons, one at a time.
+trait CouponSink: Send {
+    fn write(&mut self, coupon: &str) -> Result<(), CouponError>;
+}
+
+/// Generates `number_coupons` unique coupons on the rayon workers and passes each to `sink` on
+/// a thread of its own as soon as it is found, returning the sink once all are written.
+fn stream_unique<S: CouponSink>(
+    code_len: usize,
+    number_coupons: usize,
+    initials: &str,
+    charset: &Charset,
+    mut sink: S,
+) -> Result<S, CouponError> {
+    let coupons = parking_lot::Mutex::new(HashSet::with_capacity(number_coupons));
+    let counter = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::sync_channel::<String>(CSV_CHANNEL_CAPACITY);
+
+    thread::scope(|scope| {
+        let csv_writer = scope.spawn(move || -> Result<S, CouponError> {
+            for coupon in receiver {
+                sink.write(&coupon)?;
+            }
+            Ok(sink)
+        });
+
+        rayon::scope(|s| {
+            for _ in 0..rayon::current_num_threads() {
+                let sender = sender.clone();
+                let (coupons, counter) = (&coupons, &counter);
+                s.spawn(move |_| {
+                    let mut rng = SmallRng::from_entropy();
+                    while counter.fetch_add(1, Ordering::SeqCst) < number_coupons {
+                        let coupon = loop {
+                            let coupon = generate_coupon(&mut rng, code_len, initials, charset);
+                            if coupons.lock().insert(coupon.clone()) {
+                                break coupon;
+                            }
+                        };
+                        // The writer hung up after an error, which it reports itself
+                        if sender.send(coupon).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        // Closing the last sender ends the writer's loop
+        drop(sender);
+        csv_writer.join().expect("CSV writer thread panicked")
+    })
+}
+
+/// `coupon` followed by its [`check_character`].
+pub fn append_check_character(mut coupon: String, charset: &Charset) -> Result<String, CouponError> {
+    let check = check_character(&coupon, charset)?;
+    coupon.push(check);
+    Ok(coupon)
+}
+
+/// A coupon CSV being written: the header, then one row per coupon, with an `ExpiresAt` column
+/// when the coupons expire.
+struct CouponCsv<W: Write> {
+    writer: Writer<W>,
+    expires_at: Option<String>,
+}
+
+impl<W: Write> CouponCsv<W> {
+    /// Starts the CSV with its header; coupons valid for `valid_days` all expire on the date
+    /// that many days from today, in ISO 8601.
+    fn new(writer: W, valid_days: Option<u32>) -> Result<Self, CouponError> {
+        let expires_at = valid_days
+            .map(|days| {
+                Utc::now()
+                    .date_naive()
+                    .checked_add_days(Days::new(days.into()))
+                    .map(|date| date.format("%Y-%m-%d").to_string())
+                    .ok_or(CouponError::ExpiryOutOfRange(days))
+            })
+            .transpose()?;
+        let mut writer = Writer::from_writer(writer);
+        match expires_at {
+            Some(_) => writer.write_record(["Coupon", "ExpiresAt"])?,
+            None => writer.write_record(["Coupon"])?,
+        }
+        Ok(CouponCsv { writer, expires_at })
+    }
+
+    fn write(&mut self, coupon: &str) -> Result<(), CouponError> {
+        match &self.expires_at {
+            Some(expires_at) => self.writer.write_record([coupon, expires_at])?,
+            None => self.writer.write_record([coupon])?,
+        }
+        Ok(())
+    }
+
+    /// Flushes the CSV and hands back the underlying writer.
+    fn finish(self) -> Result<W, CouponError> {
+        self.writer.into_inner().map_err(|e| CouponError::FileCreationError(e.into_error()))
+    }
+}
+
+impl<W: Write + Send> CouponSink for CouponCsv<W> {
+    fn write(&mut self, coupon: &str) -> Result<(), CouponError> {
+        CouponCsv::write(self, coupon)
+    }
+}
+
+/// Coupon CSV files of at most `rows_per_file` rows each, the next one started when the current
+/// one is full.
+struct CouponFiles {
+    path: PathBuf,
+    rows_per_file: usize,
+    valid_days: Option<u32>,
+    current: CouponCsv<OutputFile>,
+    rows: usize,
+    written: Vec<PathBuf>,
+}
+
+impl CouponFiles {
+    /// Creates the first file right away, so that even no coupons leave a file with a header.
+    fn new(path: &Path, rows_per_file: usize, valid_days: Option<u32>) -> Result<Self, CouponError> {
+        let first = numbered_path(path, 1);
+        let current = CouponCsv::new(OutputFile::create(&first)?, valid_days)?;
+        Ok(CouponFiles {
+            path: path.to_path_buf(),
+            rows_per_file: rows_per_file.max(1),
+            valid_days,
+            current,
+            rows: 0,
+            written: vec![first],
+        })
+    }
+
+    /// Flushes the last file and returns the paths of all of them.
+    fn finish(self) -> Result<Vec<PathBuf>, CouponError> {
+        self.current.finish()?.finish()?;
+        Ok(self.written)
+    }
+}
+
+impl CouponSink for CouponFiles {
+    fn write(&mut self, coupon: &str) -> Result<(), CouponError> {
+        if self.rows == self.rows_per_file {
+            let next = numbered_path(&self.path, self.written.len() + 1);
+            let file = OutputFile::create(&next)?;
+            let full = std::mem::replace(&mut self.current, CouponCsv::new(file, self.valid_days)?);
+            full.finish()?.finish()?;
+            self.written.push(next);
+            self.rows = 0;
+        }
+        self.rows += 1;
+        self.current.write(coupon)
+    }
+}
+
+/// `path` with `_0001`, `_0002`, ... added before its extensions, e.g. `coupons_0001.csv` or
+/// `coupons_0001.csv.gz`.
+fn numbered_path(path: &Path, number: usize) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let (stem, extensions) = name.split_at(name.find('.').unwrap_or(name.len()));
+    path.with_file_name(format!("{stem}_{number:04}{extensions}"))
+}
+
+/// A file opened for a coupon CSV, gzip-compressed when its name ends in `.gz`.
+enum OutputFile {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl OutputFile {
+    /// Creates or overwrites the file at `path`.
+    fn create(path: &Path) -> std::io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(if path.extension().is_some_and(|extension| extension == "gz") {
+            OutputFile::Gzip(GzEncoder::new(file, Compression::default()))
+        } else {
+            OutputFile::Plain(file)
+        })
+    }
+
+    /// Flushes the file; a gzip file also gets its trailer, without which it reads as truncated.
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(mut file) => file.flush(),
+            OutputFile::Gzip(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutputFile::Plain(file) => file.write(buf),
+            OutputFile::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(file) => file.flush(),
+            OutputFile::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Writes the list of coupons in CSV format to any writer.
+///
+/// The writer can be a file, an in-memory `Vec<u8>`, a network stream or an upload to
+/// object storage; pass `&mut writer` to keep using it afterwards.
+///
+/// # Arguments
+///
+/// * `writer` - Where the CSV data is written.
+/// * `coupons` - A slice of coupon codes to write.
+/// * `valid_days` - When given, adds an `ExpiresAt` column holding the date that many days from
+///   today, e.g. `2025-03-31`.
+///
+/// # Returns
+///
+/// A `Result` indicating success or a `CouponError`.
+pub fn write_coupons_to_csv<W: Write>(
+    writer: W,
+    coupons: &[String],
+    valid_days: Option<u32>,
+) -> Result<(), CouponError> {
+    let mut writer = CouponCsv::new(writer, valid_days)?; // Write the header
+
+    // Write each coupon code to the CSV file
+    for coupon in coupons {
+        writer.write(coupon)?;
+    }
+
+    writer.finish()?; // Ensure all data is written to the file
+    Ok(())
+}
+
+/// Writes the list of coupons to CSV files of at most `rows_per_file` rows each, each with its
+/// own header. They are named after `path` with a number added, `coupons.csv` giving
+/// `coupons_0001.csv`, `coupons_0002.csv` and so on; the last one may hold fewer rows. With a
+/// `.gz` path every file is gzip-compressed.
+///
+/// # Returns
+///
+/// The paths of the files written, in order.
+pub fn write_coupons_to_files(
+    coupons: &[String],
+    path: &Path,
+    rows_per_file: usize,
+    valid_days: Option<u32>,
+) -> Result<Vec<PathBuf>, CouponError> {
+    let mut files = CouponFiles::new(path, rows_per_file, valid_days)?;
+    for coupon in coupons {
+        files.write(coupon)?;
+    }
+    files.finish()
+}
+
+/// Writes the list of coupons to a CSV file, gzip-compressed when `filename` ends in `.gz`.
+///
+/// # Arguments
+///
+/// * `coupons` - A slice of coupon codes to write to the file.
+/// * `filename` - The name of the output CSV file.
+/// * `valid_days` - Adds an `ExpiresAt` column; see [`write_coupons_to_csv`].
+///
+/// # Returns
+///
+/// A `Result` indicating success or a `CouponError`.
+pub fn write_coupons_to_path(
+    coupons: &[String],
+    filename: &str,
+    valid_days: Option<u32>,
+) -> Result<(), CouponError> {
+    let mut file = OutputFile::create(Path::new(filename))?; // Create or overwrite the CSV file
+    write_coupons_to_csv(&mut file, coupons, valid_days)?;
+    file.finish()?;
+    Ok(())
+}
+
+/// The reason a code does not match the expected coupon format.
+#[derive(Error, Debug)]
+pub enum FormatError {
+    /// The code does not have the expected total length.
+    #[error("expected {expected} characters, found {found}")]
+    WrongLength { expected: usize, found: usize },
+
+    /// The code does not start with the expected initials.
+    #[error("does not start with \"{0}\"")]
+    MissingPrefix(String),
+
+    /// The random part contains a character outside of the charset.
+    #[error("'{0}' is not in the charset")]
+    InvalidCharacter(char),
+}
+
+/// Checks that `code` looks like a coupon generated with the given parameters.
+///
+/// # Arguments
+///
+/// * `code` - The coupon to check.
+/// * `len` - The expected total length of the coupon.
+/// * `initials` - The expected prefix.
+/// * `charset` - The characters the random part may contain.
+///
+/// # Errors
+///
+/// Returns the first `FormatError` found.
+pub fn validate_format(code: &str, len: u16, initials: &str, charset: &Charset) -> Result<(), FormatError> {
+    let found = code.chars().count();
+    if found != len as usize {
+        return Err(FormatError::WrongLength { expected: len as usize, found });
+    }
+    let Some(random_part) = code.strip_prefix(initials) else {
+        return Err(FormatError::MissingPrefix(initials.to_string()));
+    };
+    match random_part.chars().find(|&c| charset.index_of(c).is_none()) {
+        Some(c) => Err(FormatError::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+/// Maps user input onto the Crockford alphabet: uppercases it, reads I and L as 1 and O as 0,
+/// and drops the hyphens people add for readability.
+pub fn normalize_crockford(input: &str) -> String {
+    input
+        .chars()
+        .filter(|&c| c != '-')
+        .map(|c| match c.to_ascii_uppercase() {
+            'I' | 'L' => '1',
+            'O' => '0',
+            c => c,
+        })
+        .collect()
+}
+
+/// Whether a code typed by a user matches an issued Crockford coupon, forgiving case,
+/// hyphens and the look-alike substitutions of [`normalize_crockford`].
+///
+/// The issued code is normalized too, so initials outside the alphabet (like the L and I of
+/// "LISA") compare the same way on both sides.
+pub fn matches_crockford(input: &str, issued: &str) -> bool {
+    normalize_crockford(input) == normalize_crockford(issued)
+}
+
+/// The character placed between the groups of a grouped coupon.
+pub const GROUP_SEPARATOR: char = '-';
+
+/// Computes the check character of one group: the sum of the charset indices of its characters,
+/// modulo the charset length, mapped back to a character of the charset.
+///
+/// # Errors
+///
+/// Returns `FormatError::InvalidCharacter` for a character outside of `charset`.
+pub fn group_check_character(group: &str, charset: &Charset) -> Result<char, FormatError> {
+    let mut sum = 0;
+    for c in group.chars() {
+        sum += charset.index_of(c).ok_or(FormatError::InvalidCharacter(c))?;
+    }
+    Ok(charset.chars()[sum % charset.len()] as char)
+}
+
+/// Splits `code` into groups of `group_size` characters (the last one may be shorter), appends
+/// the check character of each group, and joins the groups with `separator`.
+///
+/// A code of `n` characters makes `g = ceil(n / group_size)` groups, so the grouped code is
+/// `n + g` characters plus `g - 1` separators long: "LISAK7Q2X9" in groups of 4 becomes
+/// "LISAB-K7Q2P-X9W", 10 + 3 + 2 = 15 characters.
+///
+/// # Errors
+///
+/// Returns `FormatError::InvalidCharacter` for a character outside of `charset`.
+pub fn group_with_check(
+    code: &str,
+    group_size: usize,
+    separator: char,
+    charset: &Charset,
+) -> Result<String, FormatError> {
+    let chars: Vec<char> = code.chars().collect();
+    let mut grouped = String::with_capacity(chars.len() * 2);
+    for (i, group) in chars.chunks(group_size.max(1)).enumerate() {
+        let group: String = group.iter().collect();
+        if i > 0 {
+            grouped.push(separator);
+        }
+        let check = group_check_character(&group, charset)?;
+        grouped.push_str(&group);
+        grouped.push(check);
+    }
+    Ok(grouped)
+}
+
+/// Checks every group of a code made by [`group_with_check`] against its check character.
+///
+/// # Errors
+///
+/// Returns the zero-based indices of the groups whose check character does not match, so a
+/// mis-heard group can be asked for again on its own.
+pub fn validate_grouped(code: &str, separator: char, charset: &Charset) -> Result<(), Vec<usize>> {
+    let failed: Vec<usize> = code
+        .split(separator)
+        .enumerate()
+        .filter(|(_, group)| {
+            let mut chars = group.chars();
+            let Some(check) = chars.next_back() else {
+                return true;
+            };
+            group_check_character(chars.as_str(), charset).map_or(true, |expected| expected != check)
+        })
+        .map(|(i, _)| i)
+        .collect();
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(failed)
+    }
+}
+
+/// How many offending rows [`verify_file`] keeps for its report.
+pub const MAX_REPORTED_ROWS: usize = 5;
+
+/// The outcome of checking every coupon of a CSV file.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub valid: usize,
+    pub invalid: usize,
+    /// The first [`MAX_REPORTED_ROWS`] invalid rows: 1-based data row number, code and reason.
+    pub offending: Vec<(usize, String, FormatError)>,
+}
+
+/// Checks every coupon of a CSV file written by [`write_coupons_to_csv`] with [`validate_format`].
+///
+/// Only the first column is read, so files with an `ExpiresAt` column are checked too.
+///
+/// # Errors
+///
+/// Returns `CouponError::CsvReadError` if the file cannot be read or parsed as CSV.
+pub fn verify_file(
+    path: &Path,
+    len: u16,
+    initials: &str,
+    charset: &Charset,
+) -> Result<VerifyReport, CouponError> {
+    let mut reader = csv::Reader::from_path(path).map_err(CouponError::CsvReadError)?;
+    let mut report = VerifyReport::default();
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(CouponError::CsvReadError)?;
+        let code = record.get(0).unwrap_or_default();
+        match validate_format(code, len, initials, charset) {
+            Ok(()) => report.valid += 1,
+            Err(reason) => {
+                report.invalid += 1;
+                if report.offending.len() < MAX_REPORTED_ROWS {
+                    report.offending.push((index + 1, code.to_string(), reason));
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// The name of the manifest written next to a generated CSV file.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// An audit record of one batch of coupons written to a CSV file.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Manifest {
+    /// The total length of each coupon.
+    pub len: u16,
+    /// The prefix of each coupon.
+    pub initials: String,
+    /// The number of coupons in the file.
+    pub count: usize,
+    /// Seconds since the Unix epoch when the manifest was written.
+    pub timestamp: u64,
+    /// The hex SHA-256 of the sorted coupons, each followed by a newline.
+    pub sha256: String,
+}
+
+/// Hashes the coupons of a CSV file independently of their order.
+///
+/// # Returns
+///
+/// The number of coupons and the hex SHA-256 of the sorted coupons, each followed by a newline.
+pub fn hash_coupon_file(path: &Path) -> Result<(usize, String), CouponError> {
+    let mut reader = csv::Reader::from_path(path).map_err(CouponError::CsvReadError)?;
+    let mut coupons = reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(CouponError::CsvReadError)?;
+            Ok(record.get(0).unwrap_or_default().to_string())
+        })
+        .collect::<Result<Vec<String>, CouponError>>()?;
+    coupons.sort_unstable();
+    let mut hasher = Sha256::new();
+    for coupon in &coupons {
+        hasher.update(coupon.as_bytes());
+        hasher.update(b"\n");
+    }
+    Ok((coupons.len(), format!("{:x}", hasher.finalize())))
+}
+
+/// Writes a [`Manifest`] for the coupons in `csv_path` to [`MANIFEST_FILE`] in the same directory.
+pub fn write_manifest(csv_path: &Path, len: u16, initials: &str) -> Result<Manifest, CouponError> {
+    let (count, sha256) = hash_coupon_file(csv_path)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let manifest = Manifest { len, initials: initials.to_string(), count, timestamp, sha256 };
+    std::fs::write(csv_path.with_file_name(MANIFEST_FILE), serde_json::to_string_pretty(&manifest)?)?;
+    Ok(manifest)
+}
+
+/// Recomputes the hash of the coupons in `csv_path` and compares it with the manifest.
+///
+/// # Returns
+///
+/// `true` if the file holds exactly the coupons the manifest was written for, in any order.
+pub fn verify_manifest(manifest_path: &Path, csv_path: &Path) -> Result<bool, CouponError> {
+    let manifest: Manifest = serde_json::from_str(&std::fs::read_to_string(manifest_path)?)?;
+    let (count, sha256) = hash_coupon_file(csv_path)?;
+    Ok(count == manifest.count && sha256 == manifest.sha256)
+}
+
+/// Saturation above which [`Estimate::high_saturation`] is set: past this point most random
+/// draws collide with an existing coupon and the retry loop becomes expensive.
+pub const SATURATION_WARNING: f64 = 0.7;
+
+/// A capacity estimate for a coupon request, computed without generating anything.
+#[derive(Debug)]
+pub struct Estimate {
+    /// The number of distinct codes, saturating at `u128::MAX`.
+    pub max_combinations: u128,
+    /// The requested count divided by `max_combinations`.
+    pub saturation: f64,
+    /// The expected number of random draws needed to collect `count` unique codes.
+    pub expected_draws: f64,
+    /// The expected draws wasted on collisions, per coupon kept (0.0 means no collisions).
+    pub collision_overhead: f64,
+    /// Whether the saturation exceeds [`SATURATION_WARNING`].
+    pub high_saturation: bool,
+    /// Whether `count` unique codes exist at all.
+    pub feasible: bool,
+}
+
+/// Entropy below which the generation summary warns that coupons could be guessed.
+pub const DEFAULT_MIN_ENTROPY_BITS: f64 = 40.0;
+
+/// Returns the entropy in bits of a random code of `code_len` characters drawn uniformly from
+/// `charset_len` characters, i.e. `code_len * log2(charset_len)`.
+pub fn entropy_bits(code_len: usize, charset_len: usize) -> f64 {
+    code_len as f64 * (charset_len as f64).log2()
+}
+
+/// Approximates the harmonic number `H(n) = 1 + 1/2 + ... + 1/n`.
+fn harmonic(n: f64) -> f64 {
+    const EULER_GAMMA: f64 = 0.577_215_664_901_532_9;
+    if n < 1.0 {
+        0.0
+    } else {
+        n.ln() + EULER_GAMMA + 1.0 / (2.0 * n)
+    }
+}
+
+/// Estimates how hard it is to draw `count` unique codes of `code_len` characters.
+///
+/// Drawing the `i`-th new code from `N` combinations takes `N / (N - i)` tries on average, so
+/// collecting `count` codes takes `N * (H(N) - H(N - count))` draws in total.
+///
+/// # Arguments
+///
+/// * `code_len` - The length of the random part of each coupon.
+/// * `count` - The number of unique coupons wanted.
+/// * `charset_len` - The number of characters each position is drawn from.
+pub fn estimate(code_len: usize, count: usize, charset_len: usize) -> Estimate {
+    let max_combinations = max_combinations(charset_len, code_len).unwrap_or(u128::MAX);
+    let space = max_combinations as f64;
+    let wanted = count as f64;
+    let saturation = if max_combinations == 0 { f64::INFINITY } else { wanted / space };
+    let feasible = count as u128 <= max_combinations;
+    let expected_draws = if !feasible {
+        f64::INFINITY
+    } else if saturation < 1e-9 {
+        // Collisions are negligible, and H(N) - H(N - count) would lose all precision
+        wanted
+    } else {
+        space * (harmonic(space) - harmonic(space - wanted))
+    };
+    let collision_overhead = if count == 0 { 0.0 } else { (expected_draws / wanted - 1.0).max(0.0) };
+    Estimate {
+        max_combinations,
+        saturation,
+        expected_draws,
+        collision_overhead,
+        high_saturation: saturation > SATURATION_WARNING,
+        feasible,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbouring_seeds_share_no_coupons() {
+        let charset = Charset::alphanumeric();
+        let first = coupon_generator(10, 10_000, "", &charset, Some(42)).unwrap();
+        let second: HashSet<String> = coupon_generator(10, 10_000, "", &charset, Some(43)).unwrap().into_iter().collect();
+        assert_eq!(first.iter().filter(|coupon| second.contains(*coupon)).count(), 0);
+    }
+
+    #[test]
+    fn sub_seeds_of_neighbouring_seeds_differ() {
+        let streams = |seed| (0..SEEDED_WORKERS as u64 + 1).map(move |stream| sub_seed(seed, stream));
+        let seeds: HashSet<u64> = (40..50).flat_map(streams).collect();
+        assert_eq!(seeds.len(), 10 * (SEEDED_WORKERS + 1));
+    }
+
+    #[test]
+    fn masks_fill_each_position_from_its_class() {
+        let mask = Mask::parse("LIS\\A-{L}{L}{D}{D}{D}{L}-##-{A}");
+        assert_eq!(mask.max_combinations(), Some(26u128.pow(3) * 10u128.pow(5) * 36));
+        let mut rng = SmallRng::seed_from_u64(7);
+        for _ in 0..1000 {
+            let coupon: Vec<char> = generate_from_mask(&mut rng, &mask).chars().collect();
+            assert_eq!(coupon[..5].iter().collect::<String>(), "LISA-");
+            assert!(coupon[5..7].iter().all(char::is_ascii_uppercase));
+            assert!(coupon[7..10].iter().all(char::is_ascii_digit));
+            assert!(coupon[10].is_ascii_uppercase());
+            assert_eq!(coupon[11], '-');
+            assert!(coupon[12..14].iter().all(char::is_ascii_digit));
+            assert_eq!(coupon[14], '-');
+            assert!(CHARSET.contains(&(coupon[15] as u8)));
+        }
+    }
+
+    #[test]
+    fn digit_masks_give_only_digits() {
+        let coupons = coupon_generator_from_mask(&Mask::parse("{D}{D}{D}{D}"), 500, Some(1)).unwrap();
+        assert_eq!(coupons.iter().collect::<HashSet<_>>().len(), 500);
+        assert!(coupons.iter().all(|coupon| coupon.len() == 4 && coupon.chars().all(|c| c.is_ascii_digit())));
+    }
+
+    #[test]
+    fn masks_keep_literals_between_digits_and_letters() {
+        let mask = Mask::parse("LISA-####-AAAA");
+        assert_eq!(mask.max_combinations(), Some(10u128.pow(4) * 26u128.pow(4)));
+        let mut rng = SmallRng::seed_from_u64(11);
+        for _ in 0..1000 {
+            let coupon = generate_from_mask(&mut rng, &mask);
+            assert_eq!(coupon.len(), 14);
+            assert_eq!(&coupon[..5], "LISA-");
+            assert!(coupon[5..9].chars().all(|c| c.is_ascii_digit()));
+            assert_eq!(&coupon[9..10], "-");
+            assert!(coupon[10..].chars().all(|c| c.is_ascii_uppercase()));
+        }
+        // L and D are only classes in braces
+        assert_eq!(Mask::parse("LD-#").max_combinations(), Some(10));
+        assert!(generate_from_mask(&mut rng, &Mask::parse("LD-#")).starts_with("LD-"));
+        assert_eq!(generate_from_mask(&mut rng, &Mask::parse("\\A-\\#")), "A-#");
+    }
+
+    #[test]
+    fn masks_with_too_few_codes_are_rejected() {
+        assert!(matches!(
+            coupon_generator_from_mask(&Mask::parse("X-#"), 11, None),
+            Err(CouponError::TooManyCoupons(11, 10))
+        ));
+    }
+
+    /// Reads back the rows of a coupon CSV, without its header.
+    fn read_rows(reader: impl std::io::Read) -> Vec<Vec<String>> {
+        csv::Reader::from_reader(reader)
+            .records()
+            .map(|record| record.unwrap().iter().map(str::to_string).collect())
+            .collect()
+    }
+
+    #[test]
+    fn characters_are_drawn_uniformly() {
+        let charset = Charset::alphanumeric();
+        let coupons = coupon_generator(10, 20_000, "", &charset, Some(5)).unwrap();
+        let mut frequencies = vec![0usize; charset.len()];
+        for byte in coupons.iter().flat_map(|coupon| coupon.bytes()) {
+            frequencies[charset.index_of(byte as char).unwrap()] += 1;
+        }
+        let expected = 200_000.0 / charset.len() as f64;
+        for (&c, &count) in charset.chars().iter().zip(&frequencies) {
+            let deviation = (count as f64 - expected).abs() / expected;
+            assert!(deviation < 0.05, "'{}' came up {} times, expected about {}", c as char, count, expected);
+        }
+    }
+
+    #[test]
+    fn weighted_characters_come_up_by_weight() {
+        let charset = Charset::weighted(&[(b'A', 10.0), (b'B', 1.0)]).unwrap();
+        let mut rng = SmallRng::seed_from_u64(3);
+        let coupon = generate_coupon(&mut rng, 11_000, "", &charset);
+        let heavy = coupon.chars().filter(|&c| c == 'A').count();
+        let light = coupon.chars().filter(|&c| c == 'B').count();
+        assert_eq!(heavy + light, 11_000);
+        assert!(heavy > 5 * light, "{} heavy against {} light", heavy, light);
+    }
+
+    #[test]
+    fn weights_must_be_positive_and_distinct() {
+        assert!(matches!(Charset::weighted(&[]), Err(CouponError::InvalidWeights(_))));
+        assert!(matches!(Charset::weighted(&[(b'A', 1.0), (b'B', 0.0)]), Err(CouponError::InvalidWeights(_))));
+        assert!(matches!(Charset::weighted(&[(b'A', f64::NAN)]), Err(CouponError::InvalidWeights(_))));
+        assert!(matches!(Charset::weighted(&[(b'A', 1.0), (b'A', 2.0)]), Err(CouponError::InvalidWeights(_))));
+    }
+
+    #[test]
+    fn excluding_ambiguous_characters_keeps_the_weights() {
+        let charset = Charset::weighted(&[(b'0', 1.0), (b'X', 1.0), (b'Y', 9.0)]).unwrap();
+        let clear = charset.with_options(CharsetOptions { exclude_ambiguous: true }).unwrap();
+        assert_eq!(clear, Charset::weighted(&[(b'X', 1.0), (b'Y', 9.0)]).unwrap());
+    }
+
+    #[test]
+    fn ambiguous_characters_can_be_excluded() {
+        let charset = Charset::alphanumeric().with_options(CharsetOptions { exclude_ambiguous: true }).unwrap();
+        assert_eq!(charset.len(), CHARSET_LEN - AMBIGUOUS_CHARS.len() + 1);
+        let coupons = coupon_generator(8, 5_000, "", &charset, Some(9)).unwrap();
+        assert!(coupons.iter().all(|coupon| !coupon.bytes().any(|b| AMBIGUOUS_CHARS.contains(&b))));
+        // The feasibility check counts the reduced charset
+        let space = (charset.len() * charset.len()) as u128;
+        assert!(matches!(
+            coupon_generator(2, space as usize + 1, "", &charset, None),
+            Err(CouponError::TooManyCoupons(_, max)) if max == space
+        ));
+    }
+
+    #[test]
+    fn seeded_runs_repeat() {
+        let charset = Charset::alphanumeric();
+        let first = coupon_generator(10, 50_000, "SEED", &charset, Some(42)).unwrap();
+        assert_eq!(first, coupon_generator(10, 50_000, "SEED", &charset, Some(42)).unwrap());
+        assert_eq!(first.iter().collect::<HashSet<_>>().len(), 50_000);
+    }
+
+    #[test]
+    fn a_full_space_gives_every_code_once() {
+        let start = std::time::Instant::now();
+        let coupons = coupon_generator(4, CHARSET_LEN * CHARSET_LEN * CHARSET_LEN, "L", &Charset::default(), None).unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        let unique: HashSet<&String> = coupons.iter().collect();
+        assert_eq!(unique.len(), CHARSET_LEN.pow(3));
+        assert!(coupons.iter().all(|coupon| coupon.len() == 4 && coupon.starts_with('L')));
+    }
+
+    #[test]
+    fn requests_beyond_the_space_are_rejected() {
+        assert!(matches!(
+            coupon_generator(3, 37, "AB", &Charset::default(), None),
+            Err(CouponError::TooManyCoupons(37, 36))
+        ));
+        assert!(matches!(
+            coupon_generator(3, 1, "ABCD", &Charset::default(), None),
+            Err(CouponError::InitialsTooLong(4, 3))
+        ));
+    }
+
+    #[test]
+    fn long_codes_do_not_overflow() {
+        assert_eq!(max_combinations(CHARSET_LEN, 40), None);
+        let coupons = coupon_generator(40, 2, "LONG", &Charset::default(), None).unwrap();
+        assert_eq!(coupons.len(), 2);
+        assert!(coupons.iter().all(|coupon| coupon.len() == 40));
+    }
+
+    #[test]
+    fn resuming_a_checkpoint_completes_the_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = dir.path().join("checkpoint.txt");
+        let charset = Charset::default();
+        let first = coupon_generator_resumable(10, 200, "LISA", &charset, &checkpoint, 50, None).unwrap();
+
+        // Keep the header and the first half, and cut the next line short as a crash would
+        let content = fs::read_to_string(&checkpoint).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        let interrupted = format!("{}\n{}", lines[..101].join("\n"), &lines[101][..6]);
+        fs::write(&checkpoint, interrupted).unwrap();
+
+        let resumed = coupon_generator_resumable(10, 200, "LISA", &charset, &checkpoint, 50, None).unwrap();
+        assert_eq!(resumed.len(), 200);
+        assert_eq!(resumed[..100], first[..100]);
+        assert_eq!(resumed.iter().collect::<HashSet<_>>().len(), 200);
+        assert!(resumed.iter().all(|coupon| coupon.len() == 10 && coupon.starts_with("LISA")));
+    }
+
+    #[test]
+    fn checkpoints_of_other_runs_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = dir.path().join("checkpoint.txt");
+        coupon_generator_resumable(10, 20, "LISA", &Charset::default(), &checkpoint, 5, None).unwrap();
+        assert!(matches!(
+            coupon_generator_resumable(10, 30, "LISA", &Charset::default(), &checkpoint, 5, None),
+            Err(CouponError::CheckpointMismatch(..))
+        ));
+    }
+
+    #[test]
+    fn check_characters_catch_every_single_typo() {
+        let charset = Charset::alphanumeric();
+        let coupons = coupon_generator_with_check(10, 100, "LISA", &charset, Some(11)).unwrap();
+        assert!(coupons.iter().all(|coupon| coupon.len() == 10 && validate_coupon(coupon)));
+        let coupon = coupons[0].as_bytes();
+        for position in 0..coupon.len() {
+            for &replacement in charset.chars().iter().filter(|&&c| c != coupon[position]) {
+                let mut typo = coupon.to_vec();
+                typo[position] = replacement;
+                assert!(!validate_coupon(std::str::from_utf8(&typo).unwrap()));
+            }
+        }
+    }
+
+    #[test]
+    fn luhn_check_digit_over_digits() {
+        // The classic Luhn example: 7992739871 has check digit 3
+        assert_eq!(check_character("7992739871", &Charset::digits()).unwrap(), '3');
+        assert!(validate_coupon_with("79927398713", &Charset::digits()));
+        assert!(!validate_coupon_with("79927398714", &Charset::digits()));
+    }
+
+    #[test]
+    fn estimate_flags_high_saturation() {
+        let comfortable = estimate(6, 1_000, CHARSET_LEN);
+        assert!(comfortable.feasible);
+        assert!(!comfortable.high_saturation);
+        assert_eq!(comfortable.max_combinations, 36u128.pow(6));
+        assert!(comfortable.collision_overhead < 1e-3);
+
+        let crowded = estimate(2, 1_200, CHARSET_LEN);
+        assert!(crowded.feasible);
+        assert!(crowded.high_saturation);
+        assert!(crowded.saturation > SATURATION_WARNING);
+        assert!(crowded.collision_overhead > 1.0);
+
+        let impossible = estimate(2, 2_000, CHARSET_LEN);
+        assert!(!impossible.feasible);
+        assert_eq!(impossible.expected_draws, f64::INFINITY);
+    }
+
+    #[test]
+    fn entropy_of_six_alphanumeric_characters() {
+        let bits = entropy_bits(6, CHARSET_LEN);
+        assert!((bits - 31.02).abs() < 0.01, "{} bits", bits);
+        assert!(bits < DEFAULT_MIN_ENTROPY_BITS);
+        assert!(entropy_bits(8, CHARSET_LEN) > DEFAULT_MIN_ENTROPY_BITS);
+    }
+
+    #[test]
+    fn verify_file_flags_tampered_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("coupons.csv");
+        let charset = Charset::default();
+        let mut coupons = coupon_generator(10, 20, "LISA", &charset, Some(2)).unwrap();
+        coupons[3] = "LISA12345".to_string();
+        coupons[7] = "LIZA123456".to_string();
+        coupons[8] = "LISA12345!".to_string();
+        write_coupons_to_path(&coupons, path.to_str().unwrap(), Some(30)).unwrap();
+
+        let report = verify_file(&path, 10, "LISA", &charset).unwrap();
+        assert_eq!((report.valid, report.invalid), (17, 3));
+        let rows: Vec<usize> = report.offending.iter().map(|(row, _, _)| *row).collect();
+        assert_eq!(rows, vec![4, 8, 9]);
+        assert!(matches!(report.offending[0].2, FormatError::WrongLength { expected: 10, found: 9 }));
+        assert!(matches!(report.offending[1].2, FormatError::MissingPrefix(_)));
+        assert!(matches!(report.offending[2].2, FormatError::InvalidCharacter('!')));
+    }
+
+    #[test]
+    fn grouped_codes_catch_the_misheard_group() {
+        let charset = Charset::alphanumeric();
+        let grouped = group_with_check("LISAK7Q2X9", 4, GROUP_SEPARATOR, &charset).unwrap();
+        // 10 characters in 3 groups: 10 + 3 check characters + 2 separators
+        assert_eq!(grouped.len(), 15);
+        assert_eq!(validate_grouped(&grouped, GROUP_SEPARATOR, &charset), Ok(()));
+        for position in [0, 6, 12] {
+            let mut misheard: Vec<char> = grouped.chars().collect();
+            misheard[position] = if misheard[position] == 'B' { 'D' } else { 'B' };
+            let misheard: String = misheard.into_iter().collect();
+            assert_eq!(validate_grouped(&misheard, GROUP_SEPARATOR, &charset), Err(vec![position / 6]));
+        }
+    }
+
+    #[test]
+    fn manifests_verify_until_the_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("coupons.csv");
+        let mut coupons = coupon_generator(10, 50, "LISA", &Charset::default(), Some(4)).unwrap();
+        write_coupons_to_path(&coupons, path.to_str().unwrap(), None).unwrap();
+        let manifest = write_manifest(&path, 10, "LISA").unwrap();
+        assert_eq!(manifest.count, 50);
+        let manifest_path = dir.path().join(MANIFEST_FILE);
+        assert!(verify_manifest(&manifest_path, &path).unwrap());
+
+        // The hash does not depend on the order of the rows
+        coupons.reverse();
+        write_coupons_to_path(&coupons, path.to_str().unwrap(), None).unwrap();
+        assert!(verify_manifest(&manifest_path, &path).unwrap());
+
+        coupons[0] = "LISA000000".to_string();
+        write_coupons_to_path(&coupons, path.to_str().unwrap(), None).unwrap();
+        assert!(!verify_manifest(&manifest_path, &path).unwrap());
+    }
+
+    #[test]
+    fn crockford_codes_forgive_look_alikes() {
+        let coupons = coupon_generator(10, 500, "", &Charset::crockford(), Some(8)).unwrap();
+        assert!(coupons.iter().all(|coupon| coupon.bytes().all(|b| CROCKFORD_CHARSET.contains(&b))));
+        assert!(matches_crockford("lisa-o1ab", "LISA01AB"));
+        assert!(matches_crockford("LISAOIAB", "LISA01AB"));
+        assert!(!matches_crockford("LISA02AB", "LISA01AB"));
+    }
+
+    #[test]
+    fn csv_goes_to_any_writer() {
+        let mut buffer = Vec::new();
+        write_coupons_to_csv(&mut buffer, &["LISA1".to_string(), "LISA2".to_string()], None).unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap(), "Coupon\nLISA1\nLISA2\n");
+
+        let mut streamed = Vec::new();
+        coupon_generator_to_csv(8, 1_000, "LI", &Charset::default(), &mut streamed, None).unwrap();
+        let rows = read_rows(streamed.as_slice());
+        assert_eq!(rows.len(), 1_000);
+        assert_eq!(rows.iter().collect::<HashSet<_>>().len(), 1_000);
+    }
+
+    #[test]
+    fn expiry_column_holds_an_iso_date() {
+        let mut buffer = Vec::new();
+        write_coupons_to_csv(&mut buffer, &["LISA1".to_string()], Some(30)).unwrap();
+        let expected = (Utc::now().date_naive() + Days::new(30)).format("%Y-%m-%d").to_string();
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text, format!("Coupon,ExpiresAt\nLISA1,{}\n", expected));
+        assert!(chrono::NaiveDate::parse_from_str(&expected, "%Y-%m-%d").is_ok());
+    }
+
+    #[test]
+    fn output_rolls_over_to_numbered_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let coupons = coupon_generator(10, 10, "LISA", &Charset::default(), None).unwrap();
+        let files = write_coupons_to_files(&coupons, &dir.path().join("coupons.csv"), 4, None).unwrap();
+        let names: Vec<String> = files.iter().map(|file| file.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        assert_eq!(names, ["coupons_0001.csv", "coupons_0002.csv", "coupons_0003.csv"]);
+        let rows: Vec<usize> = files.iter().map(|file| read_rows(File::open(file).unwrap()).len()).collect();
+        assert_eq!(rows, [4, 4, 2]);
+
+        let streamed = coupon_generator_to_files(10, 10, "LISA", &Charset::default(), &dir.path().join("s.csv.gz"), 3, None).unwrap();
+        assert_eq!(streamed.len(), 4);
+        assert!(streamed[0].ends_with("s_0001.csv.gz"));
+    }
+
+    #[test]
+    fn gzip_output_reads_back_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("coupons.csv.gz");
+        let coupons = coupon_generator(10, 5_000, "LISA", &Charset::default(), None).unwrap();
+        write_coupons_to_path(&coupons, path.to_str().unwrap(), None).unwrap();
+        let rows = read_rows(flate2::read::GzDecoder::new(File::open(&path).unwrap()));
+        let read_back: Vec<String> = rows.into_iter().map(|mut row| row.remove(0)).collect();
+        assert_eq!(read_back, coupons);
+    }
+}
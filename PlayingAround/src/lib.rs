@@ -0,0 +1,4 @@
+pub mod config;
+pub mod coupon_stream;
+pub mod coupons;
+pub mod shapes;
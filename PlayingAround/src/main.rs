@@ -1,67 +1,89 @@
-use std::fmt;
-struct Circle{
-    radius: f32
-}
-struct Square{
-    side:f32
-}
-struct Rectangle{
-    width: f32,
-    height: f32
-}
-struct Triangle{
-    base:f32,
-    height:f32
-}
-enum Shape {
-    Circle(Circle),
-    Square(Square),
-    Triangle(Triangle),
-    Rectangle(Rectangle)
-}
-impl fmt::Display for Shape {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Shape::Circle(c) => write!(f, "Circle with radius {}", c.radius),
-            Shape::Square(s) => write!(f, "Square with side {}", s.side),
-            Shape::Rectangle(r) => write!(f, "Rectangle with width {} and height {}", r.width, r.height),
-            Shape::Triangle(t) => write!(f, "Triangle with base {} and height {}", t.base, t.height),
-        }
-    }
-}
-impl Shape {
-    fn area(&self) -> f32{
-        match self {
-            Shape::Circle(c) => std::f32::consts::PI * c.radius * c.radius,
-            Shape::Rectangle(r) => r.width * r.height,
-            Shape::Triangle(t) => t.base * t.height,
-            Shape::Square(s) => s.side
-        }
-    }
-}
+use std::path::Path;
+use playing_around::shapes::{
+    area_summary, count_by_variant, group_by_area, largest_shape, load_shapes, load_shapes_json, shapes_to_svg, total_area, Circle, Ellipse, MeasuredShape, Rectangle, RegularPolygon, Shape, Square, Triangle, Unit,
+};
 
 fn main() {
-    let shapes_vector: Vec<Shape> = vec![
-        Shape::Circle(Circle{radius:2.5}),
-        Shape::Square(Square{side:4.0}),
-        Shape::Rectangle(
+    let shapes_vector: Vec<Box<dyn Shape>> = vec![
+        Box::new(Circle{radius:2.5}),
+        Box::new(Square{side:4.0}),
+        Box::new(
             Rectangle{
                 width: 4.0,
                 height: 9.0
         }),
-        Shape::Triangle(Triangle{
+        Box::new(Triangle{
             base: 5.0,
             height:3.0
-        })
+        }),
+        Box::new(Ellipse{semi_major:3.0, semi_minor:2.0}),
+        Box::new(RegularPolygon{sides:6, side_length:2.0})
     ];
     for shape in shapes_vector.iter(){
         println!("the area of {} is {}",shape,shape.area());
+        if let Some(diagonal) = shape.diagonal() {
+            println!("  and its diagonal is {}", diagonal);
+        }
+        println!("  it spans at most {} with an aspect ratio of {}", shape.max_extent(), shape.aspect_ratio());
+        if let (Some(inradius), Some(circumradius)) = (shape.inradius(), shape.circumradius()) {
+            println!("  it fits circles of radius {} inside and {} around it", inradius, circumradius);
+        }
     
     let _hola: &str = "hola";
     }
+    if let Some(summary) = area_summary(&shapes_vector) {
+        println!(
+            "{} shapes: total area {}, min {}, max {}, mean {}",
+            summary.count, summary.total, summary.min, summary.max, summary.mean
+        );
+    }
+    println!("together the shapes cover {}", total_area(&shapes_vector));
+    if let Some(largest) = largest_shape(&shapes_vector) {
+        println!("the largest is the {}", largest);
+    }
+    println!("no shapes cover {} and the largest of them is {:?}", total_area::<Box<dyn Shape>>(&[]), largest_shape::<Box<dyn Shape>>(&[]));
+    for group in group_by_area(&shapes_vector, 1.0) {
+        let names: Vec<String> = group.iter().map(|shape| shape.to_string()).collect();
+        println!("about {} in area: {}", group[0].area(), names.join(", "));
+    }
+    println!("shapes per variant: {:?}", count_by_variant(&shapes_vector));
+    // A regular polygon with four sides is a square
+    let four_sided = RegularPolygon { sides: 4, side_length: 2.0 };
+    let square = Square { side: 2.0 };
+    println!(
+        "a {} covers {}, a {} covers {} (difference {})",
+        four_sided,
+        four_sided.area(),
+        square,
+        square.area(),
+        (four_sided.area() - square.area()).abs()
+    );
+    println!("{}", shapes_to_svg(&shapes_vector));
+    let tile = MeasuredShape { shape: Box::new(Square { side: 100.0 }), unit: Unit::Cm };
+    println!(
+        "a {} in cm covers {} m² or {} mm²",
+        tile.shape,
+        tile.area_in(Unit::M),
+        tile.area_in(Unit::Mm)
+    );
+    // Shapes can also come from a file, given as the first argument: JSON when it ends in .json,
+    // one shape per line otherwise
+    if let Some(path) = std::env::args().nth(1) {
+        let path = Path::new(&path);
+        let loaded = if path.extension().is_some_and(|ext| ext == "json") { load_shapes_json(path) } else { load_shapes(path) };
+        match loaded {
+            Ok(shapes) => {
+                for shape in shapes.iter() {
+                    println!("the area of {} is {}", shape, shape.area());
+                }
+            }
+            Err(e) => println!("could not load {}: {}", path.display(), e),
+        }
+    }
     //Learning to use the Option Enum
     let x:i32 = 5;
     let y:Option<i32> = Some(5);
+    #[allow(clippy::unnecessary_literal_unwrap)]
     let sum = x + y.unwrap_or(0) ;
     print!("\nthe sum was {}",sum)
 
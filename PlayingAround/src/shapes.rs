@@ -0,0 +1,776 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Circle{
+    pub radius: f32
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Square{
+    pub side:f32
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rectangle{
+    pub width: f32,
+    pub height: f32
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Triangle{
+    pub base:f32,
+    pub height:f32
+}
+/// An ellipse whose major axis lies along the x axis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ellipse{
+    pub semi_major: f32,
+    pub semi_minor: f32
+}
+/// A polygon with `sides` equal sides and equal angles, resting on one of its sides.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegularPolygon{
+    pub sides: u32,
+    pub side_length: f32
+}
+/// What every shape can tell about itself. Each kind of shape is its own type implementing this
+/// trait, so a new shape is a new type and impl rather than another arm in a central match.
+pub trait Shape: fmt::Debug + fmt::Display {
+    fn area(&self) -> f32;
+    /// The name of the shape, e.g. `"circle"`.
+    fn name(&self) -> &'static str;
+    /// The width and height of the smallest axis-aligned rectangle around the shape.
+    fn bounding_box(&self) -> (f32, f32);
+    /// An SVG element drawing the shape with the top-left corner of its bounding box at (x, y).
+    fn to_svg(&self, x: f32, y: f32) -> String;
+    /// The length of the diagonal, for the shapes that have one.
+    fn diagonal(&self) -> Option<f32> {
+        None
+    }
+    /// The radius of the largest circle touching every side from inside, for the shapes that
+    /// have one.
+    fn inradius(&self) -> Option<f32> {
+        None
+    }
+    /// The radius of the circle passing through every corner, for the shapes that have one.
+    fn circumradius(&self) -> Option<f32> {
+        None
+    }
+    /// The width of the bounding box divided by its height; 1.0 for circles and squares.
+    fn aspect_ratio(&self) -> f32 {
+        let (width, height) = self.bounding_box();
+        width / height
+    }
+    /// The longer side of the bounding box.
+    fn max_extent(&self) -> f32 {
+        let (width, height) = self.bounding_box();
+        width.max(height)
+    }
+}
+impl<S: Shape + ?Sized> Shape for Box<S> {
+    fn area(&self) -> f32 {
+        (**self).area()
+    }
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+    fn bounding_box(&self) -> (f32, f32) {
+        (**self).bounding_box()
+    }
+    fn to_svg(&self, x: f32, y: f32) -> String {
+        (**self).to_svg(x, y)
+    }
+    fn diagonal(&self) -> Option<f32> {
+        (**self).diagonal()
+    }
+    fn inradius(&self) -> Option<f32> {
+        (**self).inradius()
+    }
+    fn circumradius(&self) -> Option<f32> {
+        (**self).circumradius()
+    }
+    fn aspect_ratio(&self) -> f32 {
+        (**self).aspect_ratio()
+    }
+    fn max_extent(&self) -> f32 {
+        (**self).max_extent()
+    }
+}
+impl fmt::Display for Circle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Circle with radius {}", self.radius)
+    }
+}
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Square with side {}", self.side)
+    }
+}
+impl fmt::Display for Rectangle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rectangle with width {} and height {}", self.width, self.height)
+    }
+}
+impl fmt::Display for Triangle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Triangle with base {} and height {}", self.base, self.height)
+    }
+}
+impl fmt::Display for Ellipse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Ellipse with semi-axes {} and {}", self.semi_major, self.semi_minor)
+    }
+}
+impl fmt::Display for RegularPolygon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Regular polygon with {} sides of length {}", self.sides, self.side_length)
+    }
+}
+impl Shape for Circle {
+    fn area(&self) -> f32 {
+        std::f32::consts::PI * self.radius * self.radius
+    }
+    fn name(&self) -> &'static str {
+        "circle"
+    }
+    fn bounding_box(&self) -> (f32, f32) {
+        (2.0 * self.radius, 2.0 * self.radius)
+    }
+    fn to_svg(&self, x: f32, y: f32) -> String {
+        format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\" />", x + self.radius, y + self.radius, self.radius)
+    }
+    fn inradius(&self) -> Option<f32> {
+        Some(self.radius)
+    }
+    fn circumradius(&self) -> Option<f32> {
+        Some(self.radius)
+    }
+}
+impl Shape for Square {
+    fn area(&self) -> f32 {
+        self.side * self.side
+    }
+    fn name(&self) -> &'static str {
+        "square"
+    }
+    fn bounding_box(&self) -> (f32, f32) {
+        (self.side, self.side)
+    }
+    fn to_svg(&self, x: f32, y: f32) -> String {
+        format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" />", x, y, self.side, self.side)
+    }
+    fn diagonal(&self) -> Option<f32> {
+        Some(self.side * std::f32::consts::SQRT_2)
+    }
+    fn inradius(&self) -> Option<f32> {
+        Some(self.side / 2.0)
+    }
+    fn circumradius(&self) -> Option<f32> {
+        self.diagonal().map(|d| d / 2.0)
+    }
+}
+impl Shape for Rectangle {
+    fn area(&self) -> f32 {
+        self.width * self.height
+    }
+    fn name(&self) -> &'static str {
+        "rectangle"
+    }
+    fn bounding_box(&self) -> (f32, f32) {
+        (self.width, self.height)
+    }
+    fn to_svg(&self, x: f32, y: f32) -> String {
+        format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" />", x, y, self.width, self.height)
+    }
+    fn diagonal(&self) -> Option<f32> {
+        Some(self.width.hypot(self.height))
+    }
+    // A rectangle that is not a square has no inscribed circle, but its corners all lie on a
+    // circle of half its diagonal
+    fn circumradius(&self) -> Option<f32> {
+        self.diagonal().map(|d| d / 2.0)
+    }
+}
+// A triangle is only known by its base and height, which do not pin down its side lengths, so
+// it has neither an inradius nor a circumradius
+impl Shape for Triangle {
+    fn area(&self) -> f32 {
+        0.5 * self.base * self.height
+    }
+    fn name(&self) -> &'static str {
+        "triangle"
+    }
+    fn bounding_box(&self) -> (f32, f32) {
+        (self.base, self.height)
+    }
+    /// Drawn isosceles, with the apex above the middle of the base.
+    fn to_svg(&self, x: f32, y: f32) -> String {
+        format!(
+            "<polygon points=\"{},{} {},{} {},{}\" />",
+            x,
+            y + self.height,
+            x + self.base,
+            y + self.height,
+            x + self.base / 2.0,
+            y
+        )
+    }
+}
+// An ellipse has neither sides nor corners for a circle to touch
+impl Shape for Ellipse {
+    fn area(&self) -> f32 {
+        std::f32::consts::PI * self.semi_major * self.semi_minor
+    }
+    fn name(&self) -> &'static str {
+        "ellipse"
+    }
+    fn bounding_box(&self) -> (f32, f32) {
+        (2.0 * self.semi_major, 2.0 * self.semi_minor)
+    }
+    fn to_svg(&self, x: f32, y: f32) -> String {
+        format!(
+            "<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\" />",
+            x + self.semi_major,
+            y + self.semi_minor,
+            self.semi_major,
+            self.semi_minor
+        )
+    }
+}
+impl Shape for RegularPolygon {
+    fn area(&self) -> f32 {
+        let n = self.sides as f32;
+        0.25 * n * self.side_length * self.side_length / (std::f32::consts::PI / n).tan()
+    }
+    fn name(&self) -> &'static str {
+        "polygon"
+    }
+    fn bounding_box(&self) -> (f32, f32) {
+        let (min, max) = polygon_extent(&self.vertices());
+        (max.0 - min.0, max.1 - min.1)
+    }
+    fn to_svg(&self, x: f32, y: f32) -> String {
+        let vertices = self.vertices();
+        let (min, _) = polygon_extent(&vertices);
+        let points: Vec<String> = vertices
+            .iter()
+            .map(|(vx, vy)| format!("{},{}", x + vx - min.0, y + vy - min.1))
+            .collect();
+        format!("<polygon points=\"{}\" />", points.join(" "))
+    }
+    fn inradius(&self) -> Option<f32> {
+        Some(self.side_length / (2.0 * (std::f32::consts::PI / self.sides as f32).tan()))
+    }
+    fn circumradius(&self) -> Option<f32> {
+        Some(self.corner_radius())
+    }
+}
+impl RegularPolygon {
+    /// The radius of the circle through the corners.
+    fn corner_radius(&self) -> f32 {
+        self.side_length / (2.0 * (std::f32::consts::PI / self.sides as f32).sin())
+    }
+    /// The corners relative to the center in SVG coordinates (y pointing down), starting at the left
+    /// end of the bottom side and going clockwise.
+    pub fn vertices(&self) -> Vec<(f32, f32)> {
+        let step = 2.0 * std::f32::consts::PI / self.sides as f32;
+        // Half a step past straight down puts the first side horizontal at the bottom
+        let start = std::f32::consts::FRAC_PI_2 + step / 2.0;
+        let radius = self.corner_radius();
+        (0..self.sides)
+            .map(|k| {
+                let angle = start + k as f32 * step;
+                (radius * angle.cos(), radius * angle.sin())
+            })
+            .collect()
+    }
+}
+/// A shape of any kind, as parsed from text or read from JSON. In JSON the kind is a `type`
+/// field next to the dimensions, e.g. `{"type": "circle", "radius": 2.5}`.
+///
+/// It implements [`Shape`] by handing every call to the wrapped shape.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ShapeSpec {
+    Circle(Circle),
+    Square(Square),
+    Triangle(Triangle),
+    Rectangle(Rectangle),
+    Ellipse(Ellipse),
+    #[serde(rename = "polygon")]
+    RegularPolygon(RegularPolygon)
+}
+impl fmt::Display for ShapeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_shape(), f)
+    }
+}
+impl Shape for ShapeSpec {
+    fn area(&self) -> f32 {
+        self.as_shape().area()
+    }
+    fn name(&self) -> &'static str {
+        self.as_shape().name()
+    }
+    fn bounding_box(&self) -> (f32, f32) {
+        self.as_shape().bounding_box()
+    }
+    fn to_svg(&self, x: f32, y: f32) -> String {
+        self.as_shape().to_svg(x, y)
+    }
+    fn diagonal(&self) -> Option<f32> {
+        self.as_shape().diagonal()
+    }
+    fn inradius(&self) -> Option<f32> {
+        self.as_shape().inradius()
+    }
+    fn circumradius(&self) -> Option<f32> {
+        self.as_shape().circumradius()
+    }
+}
+impl ShapeSpec {
+    /// Checks every dimension is a positive number and a polygon has at least
+    /// [`MIN_POLYGON_SIDES`] sides, e.g. for a shape deserialized straight from JSON.
+    pub fn validate(&self) -> Result<(), ShapeError> {
+        let dimensions = match self {
+            ShapeSpec::Circle(c) => vec![c.radius],
+            ShapeSpec::Square(s) => vec![s.side],
+            ShapeSpec::Rectangle(r) => vec![r.width, r.height],
+            ShapeSpec::Triangle(t) => vec![t.base, t.height],
+            ShapeSpec::Ellipse(e) => vec![e.semi_major, e.semi_minor],
+            ShapeSpec::RegularPolygon(p) => {
+                if p.sides < MIN_POLYGON_SIDES {
+                    return Err(ShapeError::InvalidSpec(self.to_string(), "a polygon needs at least 3 sides".to_string()));
+                }
+                vec![p.side_length]
+            }
+        };
+        match dimensions.into_iter().find(|&value| !is_valid_dimension(value)) {
+            Some(value) => Err(ShapeError::InvalidSpec(self.to_string(), format!("{} is not a positive number", value))),
+            None => Ok(()),
+        }
+    }
+    /// The wrapped shape as a trait object.
+    pub fn as_shape(&self) -> &dyn Shape {
+        match self {
+            ShapeSpec::Circle(c) => c,
+            ShapeSpec::Square(s) => s,
+            ShapeSpec::Rectangle(r) => r,
+            ShapeSpec::Triangle(t) => t,
+            ShapeSpec::Ellipse(e) => e,
+            ShapeSpec::RegularPolygon(p) => p,
+        }
+    }
+}
+/// The smallest and largest coordinates among `points`.
+fn polygon_extent(points: &[(f32, f32)]) -> ((f32, f32), (f32, f32)) {
+    points.iter().fold(
+        ((f32::INFINITY, f32::INFINITY), (f32::NEG_INFINITY, f32::NEG_INFINITY)),
+        |(min, max), &(x, y)| ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y))),
+    )
+}
+/// Space left between two cells of the grid drawn by [`shapes_to_svg`], in SVG user units.
+pub const SVG_GAP: f32 = 1.0;
+/// Draws `shapes` tiled in a square-ish grid in one SVG document, sized to fit their bounding
+/// boxes: every cell is as wide as the widest shape and as tall as the tallest one.
+pub fn shapes_to_svg<S: Shape>(shapes: &[S]) -> String {
+    let columns = (shapes.len() as f32).sqrt().ceil().max(1.0) as usize;
+    let rows = shapes.len().div_ceil(columns);
+    let (cell_width, cell_height) = shapes
+        .iter()
+        .map(|shape| shape.bounding_box())
+        .fold((0.0_f32, 0.0_f32), |(w, h), (shape_w, shape_h)| (w.max(shape_w), h.max(shape_h)));
+    let mut elements = String::new();
+    for (index, shape) in shapes.iter().enumerate() {
+        let x = (index % columns) as f32 * (cell_width + SVG_GAP);
+        let y = (index / columns) as f32 * (cell_height + SVG_GAP);
+        elements.push_str("  ");
+        elements.push_str(&shape.to_svg(x, y));
+        elements.push('\n');
+    }
+    let extent = |count: usize, cell: f32| (count as f32 * (cell + SVG_GAP) - SVG_GAP).max(0.0);
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n{elements}</svg>\n",
+        w = extent(columns.min(shapes.len()), cell_width),
+        h = extent(rows, cell_height)
+    )
+}
+#[derive(Error, Debug)]
+pub enum ShapeError {
+    /// The text does not describe a shape, e.g. an unknown name or a missing dimension.
+    #[error("invalid shape \"{0}\": {1}")]
+    InvalidSpec(String, String),
+
+    /// A line of a shapes file could not be parsed.
+    #[error("line {0}: {1}")]
+    AtLine(usize, Box<ShapeError>),
+
+    /// The shapes file could not be read or written.
+    #[error("failed to read shapes: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// A JSON shapes file is not an array of shapes.
+    #[error("invalid shapes JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+/// The fewest sides a [`RegularPolygon`] can have.
+pub const MIN_POLYGON_SIDES: u32 = 3;
+fn is_valid_dimension(value: f32) -> bool {
+    value.is_finite() && value > 0.0
+}
+/// Parses a shape name followed by its dimensions, e.g. `circle 2.5`, `rectangle 4 9` or
+/// `polygon 6 2` (six sides of length 2).
+impl FromStr for ShapeSpec {
+    type Err = ShapeError;
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: String| ShapeError::InvalidSpec(spec.to_string(), reason);
+        let mut words = spec.split_whitespace();
+        let name = words.next().ok_or_else(|| invalid("no shape name".to_string()))?;
+        let dimensions = words
+            .map(|word| match word.parse::<f32>() {
+                Ok(value) if is_valid_dimension(value) => Ok(value),
+                _ => Err(invalid(format!("\"{}\" is not a positive number", word))),
+            })
+            .collect::<Result<Vec<f32>, ShapeError>>()?;
+        match (name.to_lowercase().as_str(), dimensions.as_slice()) {
+            ("circle", &[radius]) => Ok(ShapeSpec::Circle(Circle { radius })),
+            ("square", &[side]) => Ok(ShapeSpec::Square(Square { side })),
+            ("rectangle", &[width, height]) => Ok(ShapeSpec::Rectangle(Rectangle { width, height })),
+            ("triangle", &[base, height]) => Ok(ShapeSpec::Triangle(Triangle { base, height })),
+            ("ellipse", &[semi_major, semi_minor]) => Ok(ShapeSpec::Ellipse(Ellipse { semi_major, semi_minor })),
+            ("polygon", &[sides, side_length]) => {
+                if sides.fract() != 0.0 || sides < MIN_POLYGON_SIDES as f32 {
+                    return Err(invalid(format!("\"{}\" is not a whole number of at least 3 sides", sides)));
+                }
+                Ok(ShapeSpec::RegularPolygon(RegularPolygon { sides: sides as u32, side_length }))
+            }
+            ("circle" | "square", _) => Err(invalid("expected 1 dimension".to_string())),
+            ("rectangle" | "triangle" | "ellipse" | "polygon", _) => Err(invalid("expected 2 dimensions".to_string())),
+            _ => Err(invalid(format!("unknown shape \"{}\"", name))),
+        }
+    }
+}
+/// Reads one shape per line, skipping blank lines and `#` comments.
+///
+/// Stops at the first line that is not a valid shape and reports its (1-based) number.
+pub fn load_shapes(path: &Path) -> Result<Vec<ShapeSpec>, ShapeError> {
+    fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| line.parse().map_err(|e| ShapeError::AtLine(number, Box::new(e))))
+        .collect()
+}
+/// Reads a JSON array of shapes, as written by [`save_shapes_json`].
+///
+/// Unlike [`load_shapes`], dimensions are taken as they are.
+pub fn load_shapes_json(path: &Path) -> Result<Vec<ShapeSpec>, ShapeError> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+/// Writes `shapes` as a pretty-printed JSON array.
+pub fn save_shapes_json(path: &Path, shapes: &[ShapeSpec]) -> Result<(), ShapeError> {
+    Ok(fs::write(path, serde_json::to_string_pretty(shapes)?)?)
+}
+/// Length units a shape's dimensions can be expressed in.
+#[derive(Clone, Copy)]
+pub enum Unit {
+    Mm,
+    Cm,
+    M,
+}
+impl Unit {
+    pub fn in_millimeters(self) -> f32 {
+        match self {
+            Unit::Mm => 1.0,
+            Unit::Cm => 10.0,
+            Unit::M => 1000.0,
+        }
+    }
+}
+/// A shape together with the unit its dimensions are measured in.
+pub struct MeasuredShape {
+    pub shape: Box<dyn Shape>,
+    pub unit: Unit,
+}
+impl MeasuredShape {
+    /// The area in square `unit`s; areas scale with the square of the length factor.
+    pub fn area_in(&self, unit: Unit) -> f32 {
+        let factor = self.unit.in_millimeters() / unit.in_millimeters();
+        self.shape.area() * factor * factor
+    }
+}
+/// The summed area of `shapes`; 0.0 when there are none.
+pub fn total_area<S: Shape>(shapes: &[S]) -> f32 {
+    shapes.iter().map(|shape| shape.area()).fold(0.0, |total, area| total + area)
+}
+/// The shape with the largest area, the first one among equals; `None` when there are none.
+pub fn largest_shape<S: Shape>(shapes: &[S]) -> Option<&S> {
+    shapes
+        .iter()
+        .reduce(|largest, shape| if shape.area().total_cmp(&largest.area()).is_gt() { shape } else { largest })
+}
+/// Count, total, extremes and mean of the areas of a collection of shapes.
+#[derive(Debug)]
+pub struct AreaSummary {
+    pub count: usize,
+    pub total: f32,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+/// Summarizes the areas of `shapes` in a single pass, or `None` when there are no shapes.
+pub fn area_summary<S: Shape>(shapes: &[S]) -> Option<AreaSummary> {
+    let mut areas = shapes.iter().map(|shape| shape.area());
+    let first = areas.next()?;
+    let (count, total, min, max) = areas.fold((1, first, first, first), |(count, total, min, max), area| {
+        (
+            count + 1,
+            total + area,
+            if area.total_cmp(&min).is_lt() { area } else { min },
+            if area.total_cmp(&max).is_gt() { area } else { max },
+        )
+    });
+    Some(AreaSummary { count, total, min, max, mean: total / count as f32 })
+}
+/// Clusters shapes of about the same area, smallest areas first.
+///
+/// Shapes are sorted by area and each one joins the current group while its area is within
+/// `epsilon` of the group's smallest area, so no group spans more than `epsilon`.
+pub fn group_by_area<S: Shape>(shapes: &[S], epsilon: f32) -> Vec<Vec<&S>> {
+    let mut sorted: Vec<&S> = shapes.iter().collect();
+    sorted.sort_by(|a, b| a.area().total_cmp(&b.area()));
+    let mut groups: Vec<Vec<&S>> = Vec::new();
+    for shape in sorted {
+        match groups.last_mut() {
+            Some(group) if shape.area() - group[0].area() <= epsilon => group.push(shape),
+            _ => groups.push(vec![shape]),
+        }
+    }
+    groups
+}
+/// How many shapes there are of each kind, keyed by [`Shape::name`].
+pub fn count_by_variant<S: Shape>(shapes: &[S]) -> BTreeMap<&'static str, usize> {
+    let mut counts = BTreeMap::new();
+    for shape in shapes {
+        *counts.entry(shape.name()).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn sample_shapes() -> Vec<ShapeSpec> {
+        vec![
+            ShapeSpec::Circle(Circle { radius: 2.5 }),
+            ShapeSpec::Square(Square { side: 4.0 }),
+            ShapeSpec::Rectangle(Rectangle { width: 4.0, height: 9.0 }),
+            ShapeSpec::Triangle(Triangle { base: 5.0, height: 3.0 }),
+            ShapeSpec::Ellipse(Ellipse { semi_major: 3.0, semi_minor: 2.0 }),
+            ShapeSpec::RegularPolygon(RegularPolygon { sides: 6, side_length: 2.0 }),
+        ]
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < EPSILON, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn known_areas() {
+        assert_eq!(ShapeSpec::Square(Square { side: 4.0 }).area(), 16.0);
+        assert_eq!(ShapeSpec::Triangle(Triangle { base: 5.0, height: 3.0 }).area(), 7.5);
+        assert_eq!(ShapeSpec::Rectangle(Rectangle { width: 4.0, height: 9.0 }).area(), 36.0);
+        assert_close(ShapeSpec::Circle(Circle { radius: 2.0 }).area(), std::f32::consts::PI * 4.0);
+        assert_close(ShapeSpec::Ellipse(Ellipse { semi_major: 3.0, semi_minor: 2.0 }).area(), std::f32::consts::PI * 6.0);
+    }
+
+    #[test]
+    fn boxed_shapes_answer_like_their_specs() {
+        let boxed: Vec<Box<dyn Shape>> = vec![
+            Box::new(Circle { radius: 2.5 }),
+            Box::new(Square { side: 4.0 }),
+            Box::new(Rectangle { width: 4.0, height: 9.0 }),
+            Box::new(Triangle { base: 5.0, height: 3.0 }),
+            Box::new(Ellipse { semi_major: 3.0, semi_minor: 2.0 }),
+            Box::new(RegularPolygon { sides: 6, side_length: 2.0 }),
+        ];
+        let specs = sample_shapes();
+        for (shape, spec) in boxed.iter().zip(&specs) {
+            assert_eq!(shape.name(), spec.name());
+            assert_eq!(shape.to_string(), spec.to_string());
+            assert_eq!(shape.area(), spec.area());
+            assert_eq!(shape.diagonal(), spec.diagonal());
+            assert_eq!(shape.to_svg(0.0, 0.0), spec.to_svg(0.0, 0.0));
+        }
+        assert_eq!(total_area(&boxed), total_area(&specs));
+        assert_eq!(shapes_to_svg(&boxed), shapes_to_svg(&specs));
+        assert_eq!(count_by_variant(&boxed), count_by_variant(&specs));
+    }
+
+    #[test]
+    fn four_sided_polygon_is_a_square() {
+        let polygon = RegularPolygon { sides: 4, side_length: 2.0 };
+        assert_close(polygon.area(), Square { side: 2.0 }.area());
+    }
+
+    #[test]
+    fn area_summary_of_the_sample() {
+        let shapes = sample_shapes();
+        let areas: Vec<f32> = shapes.iter().map(|shape| shape.area()).collect();
+        let summary = area_summary(&shapes).unwrap();
+        assert_eq!(summary.count, 6);
+        assert_close(summary.total, areas.iter().sum());
+        assert_eq!(summary.min, 7.5);
+        assert_eq!(summary.max, 36.0);
+        assert_close(summary.mean, summary.total / 6.0);
+        assert!(area_summary::<ShapeSpec>(&[]).is_none());
+    }
+
+    #[test]
+    fn area_in_converts_by_the_square_of_the_unit() {
+        let tile = MeasuredShape { shape: Box::new(Square { side: 100.0 }), unit: Unit::Cm };
+        assert_close(tile.area_in(Unit::M), 1.0);
+        assert_close(tile.area_in(Unit::Mm), 1_000_000.0);
+        assert_close(tile.area_in(Unit::Cm), 10_000.0);
+    }
+
+    #[test]
+    fn diagonal_of_rectangular_shapes_only() {
+        assert_close(ShapeSpec::Rectangle(Rectangle { width: 3.0, height: 4.0 }).diagonal().unwrap(), 5.0);
+        assert_close(ShapeSpec::Square(Square { side: 1.0 }).diagonal().unwrap(), std::f32::consts::SQRT_2);
+        assert_eq!(ShapeSpec::Circle(Circle { radius: 1.0 }).diagonal(), None);
+        assert_eq!(ShapeSpec::Triangle(Triangle { base: 3.0, height: 4.0 }).diagonal(), None);
+    }
+
+    #[test]
+    fn inradius_and_circumradius() {
+        let circle = ShapeSpec::Circle(Circle { radius: 2.0 });
+        assert_eq!(circle.inradius(), Some(2.0));
+        assert_eq!(circle.circumradius(), Some(2.0));
+        let square = ShapeSpec::Square(Square { side: 2.0 });
+        assert_eq!(square.inradius(), Some(1.0));
+        assert_close(square.circumradius().unwrap(), std::f32::consts::SQRT_2);
+        let rectangle = ShapeSpec::Rectangle(Rectangle { width: 3.0, height: 4.0 });
+        assert_eq!(rectangle.inradius(), None);
+        assert_close(rectangle.circumradius().unwrap(), 2.5);
+        let triangle = ShapeSpec::Triangle(Triangle { base: 3.0, height: 4.0 });
+        assert_eq!(triangle.inradius(), None);
+        assert_eq!(triangle.circumradius(), None);
+    }
+
+    #[test]
+    fn parses_shape_specs() {
+        assert_eq!("circle 2.5".parse::<ShapeSpec>().unwrap(), ShapeSpec::Circle(Circle { radius: 2.5 }));
+        assert_eq!(
+            "Polygon 6 2".parse::<ShapeSpec>().unwrap(),
+            ShapeSpec::RegularPolygon(RegularPolygon { sides: 6, side_length: 2.0 })
+        );
+        assert!("circle".parse::<ShapeSpec>().is_err());
+        assert!("square -1".parse::<ShapeSpec>().is_err());
+        assert!("polygon 2.5 1".parse::<ShapeSpec>().is_err());
+        assert!("hexagon 1".parse::<ShapeSpec>().is_err());
+    }
+
+    #[test]
+    fn loads_the_sample_shapes_file() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("shapes.txt");
+        assert_eq!(load_shapes(&path).unwrap(), sample_shapes());
+    }
+
+    #[test]
+    fn load_shapes_reports_the_failing_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shapes.txt");
+        fs::write(&path, "# comment\n\ncircle 1\nsquare two\n").unwrap();
+        match load_shapes(&path) {
+            Err(ShapeError::AtLine(4, _)) => {}
+            other => panic!("expected an error on line 4, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn circle_svg_has_its_radius() {
+        let svg = ShapeSpec::Circle(Circle { radius: 2.5 }).to_svg(1.0, 1.0);
+        assert!(svg.starts_with("<circle"));
+        assert!(svg.contains("r=\"2.5\""));
+        assert!(svg.contains("cx=\"3.5\""));
+    }
+
+    #[test]
+    fn svg_document_holds_every_shape() {
+        let document = shapes_to_svg(&sample_shapes());
+        assert!(document.starts_with("<svg"));
+        assert!(document.trim_end().ends_with("</svg>"));
+        assert_eq!(document.matches("<polygon").count(), 2);
+        assert_eq!(document.matches("<rect").count(), 2);
+    }
+
+    #[test]
+    fn groups_by_area_and_counts_variants() {
+        let shapes = vec![
+            ShapeSpec::Square(Square { side: 2.0 }),
+            ShapeSpec::Rectangle(Rectangle { width: 1.0, height: 4.0 }),
+            ShapeSpec::Circle(Circle { radius: 10.0 }),
+            ShapeSpec::Square(Square { side: 2.1 }),
+        ];
+        let groups = group_by_area(&shapes, 0.5);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].len(), 3);
+        assert_eq!(groups[1], vec![&shapes[2]]);
+        let counts = count_by_variant(&shapes);
+        assert_eq!(counts.get("square"), Some(&2));
+        assert_eq!(counts.get("rectangle"), Some(&1));
+        assert_eq!(counts.get("circle"), Some(&1));
+        assert_eq!(counts.get("triangle"), None);
+    }
+
+    #[test]
+    fn aspect_ratio_and_max_extent() {
+        let rectangle = ShapeSpec::Rectangle(Rectangle { width: 4.0, height: 9.0 });
+        assert_close(rectangle.aspect_ratio(), 4.0 / 9.0);
+        assert_eq!(rectangle.max_extent(), 9.0);
+        assert_eq!(ShapeSpec::Square(Square { side: 3.0 }).aspect_ratio(), 1.0);
+        assert_eq!(ShapeSpec::Circle(Circle { radius: 3.0 }).aspect_ratio(), 1.0);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let shapes = sample_shapes();
+        let json = serde_json::to_string(&shapes).unwrap();
+        assert!(json.contains("\"type\":\"circle\""));
+        assert_eq!(serde_json::from_str::<Vec<ShapeSpec>>(&json).unwrap(), shapes);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shapes.json");
+        save_shapes_json(&path, &shapes).unwrap();
+        assert_eq!(load_shapes_json(&path).unwrap(), shapes);
+    }
+
+    #[test]
+    fn validate_rejects_bad_dimensions() {
+        assert!(ShapeSpec::Circle(Circle { radius: 1.0 }).validate().is_ok());
+        assert!(ShapeSpec::Circle(Circle { radius: 0.0 }).validate().is_err());
+        assert!(ShapeSpec::Rectangle(Rectangle { width: 1.0, height: f32::NAN }).validate().is_err());
+        assert!(ShapeSpec::RegularPolygon(RegularPolygon { sides: 2, side_length: 1.0 }).validate().is_err());
+    }
+
+    #[test]
+    fn total_area_and_largest_shape() {
+        assert_eq!(total_area::<ShapeSpec>(&[]), 0.0);
+        assert!(largest_shape::<ShapeSpec>(&[]).is_none());
+        let shapes = vec![
+            ShapeSpec::Square(Square { side: 3.0 }),
+            ShapeSpec::Rectangle(Rectangle { width: 1.0, height: 9.0 }),
+            ShapeSpec::Triangle(Triangle { base: 2.0, height: 2.0 }),
+        ];
+        assert_eq!(total_area(&shapes), 20.0);
+        // The square and the rectangle tie at 9.0; the first one wins
+        assert_eq!(largest_shape(&shapes), Some(&shapes[0]));
+    }
+}
@@ -0,0 +1,28 @@
+//! Runs the pig latin binary as a filter and with arguments.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Runs the translator with `args`, writes `input` to its stdin and returns its stdout.
+fn run(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pig_latin"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start pig_latin");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn translates_every_line_of_stdin() {
+    assert_eq!(run(&[], "hello world\nHello, Lisa!\n"), "ello-hay orld-way\nEllo-hay, Isa-lay!\n");
+}
+
+#[test]
+fn arguments_are_translated_instead_of_stdin() {
+    assert_eq!(run(&["string", "apple"], "ignored\n"), "ing-stray apple-hay\n");
+}
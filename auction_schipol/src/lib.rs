@@ -0,0 +1,456 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use serde::{Deserialize, Serialize};
+use csv::Writer;
+use rusqlite::{params, Connection};
+use url::Url;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+// Field names mirror the JSON keys of the auction site.
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AuctionItem {
+    pub Id: i64,
+    pub AuctioneerID: String,
+    pub Auction: String,
+    pub AuctSessionID: i64,
+    pub AuctSessionName: String,
+    pub GoedID: i64,
+    pub Lotnr: String,
+    pub Description: String,
+    pub LowEstimate: String,
+    pub HighEstimate: String,
+    pub Search: String,
+    pub ImageURL: String,
+    pub datumTot: String,
+    pub LowEstimateNum: i64,
+    /// `LowEstimate` as a number, `None` when it could not be parsed; see [`parse_estimate`].
+    #[serde(default)]
+    pub low_estimate_value: Option<f64>,
+    /// `HighEstimate` as a number, `None` when it could not be parsed; see [`parse_estimate`].
+    #[serde(default)]
+    pub high_estimate_value: Option<f64>,
+}
+
+/// The header row of the combined CSV, in the order of [`AuctionItem::csv_record`].
+pub const CSV_HEADER: [&str; 16] = [
+    "Id",
+    "AuctioneerID",
+    "Auction",
+    "AuctSessionID",
+    "AuctSessionName",
+    "GoedID",
+    "Lotnr",
+    "Description",
+    "LowEstimate",
+    "HighEstimate",
+    "Search",
+    "ImageURL",
+    "datumTot",
+    "LowEstimateNum",
+    "low_estimate_value",
+    "high_estimate_value",
+];
+
+impl AuctionItem {
+    /// The fields of the item as one CSV row, matching [`CSV_HEADER`].
+    pub fn csv_record(&self) -> [String; 16] {
+        [
+            self.Id.to_string(),
+            self.AuctioneerID.clone(),
+            self.Auction.clone(),
+            self.AuctSessionID.to_string(),
+            self.AuctSessionName.clone(),
+            self.GoedID.to_string(),
+            self.Lotnr.clone(),
+            self.Description.clone(),
+            self.LowEstimate.clone(),
+            self.HighEstimate.clone(),
+            self.Search.clone(),
+            self.ImageURL.clone(),
+            self.datumTot.clone(),
+            self.LowEstimateNum.to_string(),
+            self.low_estimate_value.map(|value| value.to_string()).unwrap_or_default(),
+            self.high_estimate_value.map(|value| value.to_string()).unwrap_or_default(),
+        ]
+    }
+
+    /// Fills `low_estimate_value` and `high_estimate_value` from the estimate strings.
+    pub fn parse_estimates(&mut self) {
+        self.low_estimate_value = parse_estimate(&self.LowEstimate);
+        self.high_estimate_value = parse_estimate(&self.HighEstimate);
+    }
+
+    /// When the lot's auction ends, `None` when `datumTot` could not be parsed; see [`parse_datetime`].
+    pub fn ended_at(&self) -> Option<DateTime<Utc>> {
+        parse_datetime(&self.datumTot)
+    }
+
+    /// Whether `ImageURL` is an absolute http(s) URL with a host.
+    pub fn has_image(&self) -> bool {
+        Url::parse(self.ImageURL.trim())
+            .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some())
+    }
+}
+
+/// Parses an estimate written the European way, e.g. "€ 1.200,-" or "1.250,50 EUR".
+///
+/// Currency symbols, letters, spaces and a trailing dash are dropped, `.` is read as a thousands
+/// separator and `,` as the decimal comma. Returns `None` when no number is left.
+pub fn parse_estimate(text: &str) -> Option<f64> {
+    let digits: String = text.chars().filter(|c| c.is_ascii_digit() || matches!(c, '.' | ',')).collect();
+    // "1.200,-" keeps a dangling decimal comma once the dash is gone
+    let digits = digits.trim_end_matches(',');
+    digits.replace('.', "").replace(',', ".").parse().ok()
+}
+
+/// Parses a timestamp such as "2025-01-27T16:00:28+00:00".
+///
+/// Timestamps without an offset, with a `T` or a space between date and time, and bare dates
+/// (taken as midnight) are read as UTC.
+pub fn parse_datetime(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"]
+        .into_iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))
+        .map(|datetime| datetime.and_utc())
+}
+
+/// Parses one JSON dump of the auction site, an array of items, and parses their estimates.
+pub fn parse_items(json: &[u8]) -> serde_json::Result<Vec<AuctionItem>> {
+    let mut items: Vec<AuctionItem> = serde_json::from_slice(json)?;
+    for item in items.iter_mut() {
+        item.parse_estimates();
+    }
+    Ok(items)
+}
+
+/// Writes the header and one row per item, then flushes the writer.
+pub fn write_csv<W: Write>(writer: &mut Writer<W>, items: &[AuctionItem]) -> csv::Result<()> {
+    writer.write_record(CSV_HEADER)?;
+    for item in items {
+        writer.write_record(item.csv_record())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Replaces the `items` table of `connection` with one row per item, in a single transaction,
+/// and indexes it on `Id` and the parsed low estimate.
+///
+/// Columns are named after [`CSV_HEADER`]; ids and whole-number fields are `INTEGER`, the parsed
+/// estimates `REAL` and `NULL` when they could not be parsed, everything else `TEXT`.
+pub fn write_sqlite(connection: &mut Connection, items: &[AuctionItem]) -> rusqlite::Result<()> {
+    let transaction = connection.transaction()?;
+    transaction.execute_batch(
+        "DROP TABLE IF EXISTS items;
+         CREATE TABLE items (
+             Id INTEGER NOT NULL,
+             AuctioneerID TEXT NOT NULL,
+             Auction TEXT NOT NULL,
+             AuctSessionID INTEGER NOT NULL,
+             AuctSessionName TEXT NOT NULL,
+             GoedID INTEGER NOT NULL,
+             Lotnr TEXT NOT NULL,
+             Description TEXT NOT NULL,
+             LowEstimate TEXT NOT NULL,
+             HighEstimate TEXT NOT NULL,
+             Search TEXT NOT NULL,
+             ImageURL TEXT NOT NULL,
+             datumTot TEXT NOT NULL,
+             LowEstimateNum INTEGER NOT NULL,
+             low_estimate_value REAL,
+             high_estimate_value REAL
+         );",
+    )?;
+    {
+        let mut insert = transaction.prepare(
+            "INSERT INTO items VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        )?;
+        for item in items {
+            insert.execute(params![
+                item.Id,
+                item.AuctioneerID,
+                item.Auction,
+                item.AuctSessionID,
+                item.AuctSessionName,
+                item.GoedID,
+                item.Lotnr,
+                item.Description,
+                item.LowEstimate,
+                item.HighEstimate,
+                item.Search,
+                item.ImageURL,
+                item.datumTot,
+                item.LowEstimateNum,
+                item.low_estimate_value,
+                item.high_estimate_value,
+            ])?;
+        }
+    }
+    // Indexing once after the bulk insert is cheaper than keeping the indexes up to date row by row
+    transaction.execute_batch(
+        "CREATE INDEX items_id ON items (Id);
+         CREATE INDEX items_low_estimate_value ON items (low_estimate_value);",
+    )?;
+    transaction.commit()
+}
+
+/// Conversion rates into the base currency, keyed by currency symbol or code (e.g. "€", "USD").
+pub type RateTable = HashMap<String, f64>;
+
+/// Splits an estimate such as "€ 400", "$400" or "400 USD" into its currency and amount.
+///
+/// The amount is read like [`parse_estimate`], so "€ 1.200,-" is 1200. Returns `None` as currency
+/// when the estimate is a bare number, and `None` as amount when what remains is not a number.
+pub fn split_currency(estimate: &str) -> (Option<&str>, Option<f64>) {
+    let estimate = estimate.trim();
+    let (currency, amount) = match estimate.find(|c: char| c.is_ascii_digit()) {
+        Some(0) => {
+            let end = estimate.rfind(|c: char| c.is_ascii_digit()).map_or(0, |i| i + 1);
+            (estimate[end..].trim(), &estimate[..end])
+        }
+        Some(start) => (estimate[..start].trim(), &estimate[start..]),
+        None => (estimate, ""),
+    };
+    // The dash of "€ -" or "1.200,- EUR" belongs to neither the currency nor the amount
+    let currency = currency.trim_matches(|c: char| c.is_whitespace() || matches!(c, ',' | '.' | '-'));
+    let currency = (!currency.is_empty()).then_some(currency);
+    (currency, parse_estimate(amount))
+}
+
+/// Why [`normalize_currencies`] left an item's estimates as they were.
+#[derive(Debug, PartialEq)]
+pub enum Unconverted {
+    /// An estimate carries a currency missing from the rate table.
+    UnknownCurrency(String),
+    /// An estimate has a known currency but no amount that parses.
+    UnparseableAmount(String),
+}
+
+impl std::fmt::Display for Unconverted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unconverted::UnknownCurrency(currency) => write!(f, "unknown currency {:?}", currency),
+            Unconverted::UnparseableAmount(estimate) => write!(f, "unparseable amount {:?}", estimate),
+        }
+    }
+}
+
+/// Converts the estimates carrying a known currency into the base currency: `LowEstimate` into
+/// `low_estimate_value` and, rounded, `LowEstimateNum`, and `HighEstimate` into
+/// `high_estimate_value`. Bare numbers are taken to already be in the base currency.
+///
+/// Items are left untouched when `LowEstimate` or `HighEstimate` has a currency missing from
+/// `rates`, and an estimate is left as it was when it has a currency but no amount; those are
+/// returned with their `Id` so they can be reported.
+pub fn normalize_currencies(items: &mut [AuctionItem], rates: &RateTable) -> Vec<(i64, Unconverted)> {
+    let mut unconverted = Vec::new();
+    for item in items.iter_mut() {
+        let (low_currency, low_amount) = split_currency(&item.LowEstimate);
+        let (high_currency, high_amount) = split_currency(&item.HighEstimate);
+        let missing: Vec<&str> = [low_currency, high_currency]
+            .into_iter()
+            .flatten()
+            .filter(|currency| !rates.contains_key(*currency))
+            .collect();
+        if !missing.is_empty() {
+            for currency in missing {
+                unconverted.push((item.Id, Unconverted::UnknownCurrency(currency.to_string())));
+            }
+            continue;
+        }
+        match (low_currency, low_amount) {
+            (Some(currency), Some(amount)) => {
+                let value = amount * rates[currency];
+                item.low_estimate_value = Some(value);
+                item.LowEstimateNum = value.round() as i64;
+            }
+            (Some(_), None) => unconverted.push((item.Id, Unconverted::UnparseableAmount(item.LowEstimate.clone()))),
+            (None, _) => {}
+        }
+        match (high_currency, high_amount) {
+            (Some(currency), Some(amount)) => item.high_estimate_value = Some(amount * rates[currency]),
+            (Some(_), None) => unconverted.push((item.Id, Unconverted::UnparseableAmount(item.HighEstimate.clone()))),
+            (None, _) => {}
+        }
+    }
+    unconverted
+}
+
+/// Lot count and summed `LowEstimateNum` of one auction session.
+#[derive(Debug)]
+pub struct SessionTotal {
+    pub session: String,
+    pub lots: usize,
+    pub total_low_estimate: i64,
+}
+
+/// Rolls the items up per `AuctSessionName`, largest total first; equal totals are ordered by name.
+pub fn session_totals(items: &[AuctionItem]) -> Vec<SessionTotal> {
+    let mut sessions: BTreeMap<&str, (usize, i64)> = BTreeMap::new();
+    for item in items {
+        let (lots, total) = sessions.entry(item.AuctSessionName.as_str()).or_default();
+        *lots += 1;
+        *total += item.LowEstimateNum;
+    }
+    let mut totals: Vec<SessionTotal> = sessions
+        .into_iter()
+        .map(|(session, (lots, total_low_estimate))| SessionTotal { session: session.to_string(), lots, total_low_estimate })
+        .collect();
+    // The sort is stable, so sessions with equal totals keep the name order of the map
+    totals.sort_by_key(|total| std::cmp::Reverse(total.total_low_estimate));
+    totals
+}
+
+/// Spread of one numeric estimate field; every value is zero when no item has one.
+#[derive(Debug, Default, Serialize)]
+pub struct EstimateStats {
+    pub count: usize,
+    pub min: f64,
+    pub median: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl EstimateStats {
+    fn from_values(mut values: Vec<f64>) -> Self {
+        if values.is_empty() {
+            return EstimateStats::default();
+        }
+        values.sort_by(f64::total_cmp);
+        let count = values.len();
+        let median = if count.is_multiple_of(2) {
+            (values[count / 2 - 1] + values[count / 2]) / 2.0
+        } else {
+            values[count / 2]
+        };
+        EstimateStats {
+            count,
+            min: values[0],
+            median,
+            max: values[count - 1],
+            mean: values.iter().sum::<f64>() / count as f64,
+        }
+    }
+}
+
+/// Aggregate figures of a set of combined items.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub items: usize,
+    pub auctions: usize,
+    pub low_estimate: EstimateStats,
+    pub high_estimate: EstimateStats,
+}
+
+/// Counts the items and distinct auctions and describes the parsed estimates, leaving out the
+/// items whose estimate could not be parsed.
+pub fn summarize(items: &[AuctionItem]) -> Summary {
+    let auctions: HashSet<&str> = items.iter().map(|item| item.Auction.as_str()).collect();
+    Summary {
+        items: items.len(),
+        auctions: auctions.len(),
+        low_estimate: EstimateStats::from_values(items.iter().filter_map(|item| item.low_estimate_value).collect()),
+        high_estimate: EstimateStats::from_values(items.iter().filter_map(|item| item.high_estimate_value).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: i64, low: &str, high: &str) -> AuctionItem {
+        AuctionItem {
+            Id: id,
+            AuctioneerID: String::new(),
+            Auction: String::new(),
+            AuctSessionID: 0,
+            AuctSessionName: String::new(),
+            GoedID: 0,
+            Lotnr: String::new(),
+            Description: String::new(),
+            LowEstimate: low.to_string(),
+            HighEstimate: high.to_string(),
+            Search: String::new(),
+            ImageURL: String::new(),
+            datumTot: String::new(),
+            LowEstimateNum: 0,
+            low_estimate_value: None,
+            high_estimate_value: None,
+        }
+    }
+
+    #[test]
+    fn split_currency_reads_european_amounts() {
+        assert_eq!(split_currency("€ 1.200,-"), (Some("€"), Some(1200.0)));
+        assert_eq!(split_currency("1.250,50 EUR"), (Some("EUR"), Some(1250.5)));
+        assert_eq!(split_currency("1.200,- USD"), (Some("USD"), Some(1200.0)));
+        assert_eq!(split_currency("$400"), (Some("$"), Some(400.0)));
+        assert_eq!(split_currency("400"), (None, Some(400.0)));
+        assert_eq!(split_currency("€ -"), (Some("€"), None));
+    }
+
+    #[test]
+    fn normalize_currencies_converts_and_reports() {
+        let rates: RateTable = [("€".to_string(), 1.0), ("USD".to_string(), 0.5)].into_iter().collect();
+        let mut items = vec![
+            item(1, "€ 1.200,-", "€ 1.500,-"),
+            item(2, "400 USD", "600 USD"),
+            item(3, "£ 100", "£ 200"),
+            item(4, "€ -", "€ -"),
+            item(5, "300", "400"),
+        ];
+        items[4].LowEstimateNum = 300;
+        for item in items.iter_mut() {
+            item.parse_estimates();
+        }
+        let unconverted = normalize_currencies(&mut items, &rates);
+        assert_eq!(items.iter().map(|item| item.LowEstimateNum).collect::<Vec<_>>(), [1200, 200, 0, 0, 300]);
+        let values = |item: &AuctionItem| (item.low_estimate_value, item.high_estimate_value);
+        assert_eq!(values(&items[0]), (Some(1200.0), Some(1500.0)));
+        assert_eq!(values(&items[1]), (Some(200.0), Some(300.0)));
+        // Unknown currencies and bare numbers keep the values parse_estimates gave them
+        assert_eq!(values(&items[2]), (Some(100.0), Some(200.0)));
+        assert_eq!(values(&items[4]), (Some(300.0), Some(400.0)));
+        assert_eq!(
+            unconverted,
+            [
+                (3, Unconverted::UnknownCurrency("£".to_string())),
+                (3, Unconverted::UnknownCurrency("£".to_string())),
+                (4, Unconverted::UnparseableAmount("€ -".to_string())),
+                (4, Unconverted::UnparseableAmount("€ -".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn has_image_needs_an_http_url_with_a_host() {
+        let with_url = |url: &str| AuctionItem { ImageURL: url.to_string(), ..item(1, "", "") };
+        for url in ["https://example.com/7.jpg", " http://example.com/7.jpg "] {
+            assert!(with_url(url).has_image(), "{:?}", url);
+        }
+        for url in ["", "   ", "example.com/7.jpg", "http://", "ftp://example.com/7.jpg", "javascript:alert(1)"] {
+            assert!(!with_url(url).has_image(), "{:?}", url);
+        }
+    }
+
+    #[test]
+    fn session_totals_sum_each_session_largest_first() {
+        let lot = |session: &str, low: i64| AuctionItem {
+            AuctSessionName: session.to_string(),
+            LowEstimateNum: low,
+            ..item(1, "", "")
+        };
+        let items = [lot("Morning", 100), lot("Evening", 400), lot("Morning", 250), lot("Evening", 50)];
+        let totals: Vec<(String, usize, i64)> = session_totals(&items)
+            .into_iter()
+            .map(|total| (total.session, total.lots, total.total_low_estimate))
+            .collect();
+        assert_eq!(totals, [("Evening".to_string(), 2, 450), ("Morning".to_string(), 2, 350)]);
+    }
+}
@@ -1,101 +1,898 @@
 use std::fs::{self, File};
-use std::io::Write;
-use std::path::Path;
-use serde::{Deserialize, Serialize};
-use serde_json;
-use csv::Writer;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+use csv::{QuoteStyle, Writer, WriterBuilder};
 use anyhow::{Result, Context};
+use serde::{Deserialize, Serialize};
+use clap::{Parser, ValueEnum};
+use futures::stream::{self, StreamExt};
+use chrono::{DateTime, Utc};
+use auction_schipol::{normalize_currencies, parse_datetime, parse_items, session_totals, summarize, write_sqlite, AuctionItem, RateTable, Summary, CSV_HEADER};
+
+/// The sample dumps that ship with the crate, used when no directory is passed.
+const DEFAULT_INPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/auction_json");
+
+#[derive(Parser)]
+#[command(about = "Combine Schiphol auction JSON dumps into one JSON and one CSV file")]
+struct Args {
+    /// Directory holding the JSON dumps to combine
+    #[arg(long, default_value = DEFAULT_INPUT_DIR)]
+    input_dir: PathBuf,
+
+    /// Directory the combined files and reports are written to [default: the input directory]
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+
+    /// Print how many items have an empty or non-positive value, per field
+    #[arg(long)]
+    quality_report: bool,
+
+    /// Send a HEAD request to every image URL and write the status codes to image_check.csv
+    #[arg(long)]
+    check_images: bool,
+
+    /// Maximum number of image checks in flight at once
+    #[arg(long, default_value_t = 16)]
+    check_concurrency: usize,
+
+    /// Seconds to wait for each image check before reporting a timeout
+    #[arg(long, default_value_t = 10)]
+    check_timeout: u64,
+
+    /// Field separator of the CSV files: comma, semicolon, tab or any single-byte character
+    #[arg(long, default_value = "comma", value_parser = parse_delimiter)]
+    delimiter: u8,
+
+    /// Explore the combined items interactively instead of writing output files
+    #[arg(long)]
+    search: bool,
+
+    /// Also write a self-contained HTML gallery of the lots to this file
+    #[arg(long, value_name = "PATH")]
+    html: Option<PathBuf>,
+
+    /// Also write the combined items to an `items` table in this SQLite database, replacing the
+    /// table if it exists
+    #[arg(long, value_name = "PATH")]
+    sqlite: Option<PathBuf>,
+
+    /// Also write the lot count and total low estimate of each session to session_totals.csv
+    #[arg(long)]
+    session_totals: bool,
+
+    /// Print the item and auction counts and the spread of the parsed estimates, and write them
+    /// to summary.json
+    #[arg(long)]
+    summary: bool,
+
+    /// Keep only the lots whose ImageURL is a well-formed http(s) URL
+    #[arg(long)]
+    images_only: bool,
+
+    /// Only re-parse the JSON files that changed since the last incremental run, reusing the
+    /// items cached in the directory's state file for the others
+    #[arg(long)]
+    incremental: bool,
+
+    /// JSON object of conversion rates into the base currency, keyed by currency symbol or code,
+    /// used to convert the estimates that carry a currency before the lots are filtered
+    #[arg(long, value_name = "PATH")]
+    rates: Option<PathBuf>,
+
+    /// Keep only the lots whose parsed LowEstimate is at least this amount; lots whose estimate
+    /// cannot be parsed are dropped when either estimate bound is set
+    #[arg(long, value_name = "AMOUNT")]
+    min_estimate: Option<f64>,
+
+    /// Keep only the lots whose parsed LowEstimate is at most this amount
+    #[arg(long, value_name = "AMOUNT")]
+    max_estimate: Option<f64>,
+
+    /// Keep only the lots whose datumTot is at or after this time, e.g. 2025-01-27 or
+    /// 2025-01-27T16:00:00+01:00 (times without an offset are UTC)
+    #[arg(long, value_name = "DATETIME", value_parser = parse_datetime_arg)]
+    ended_after: Option<DateTime<Utc>>,
+
+    /// Keep only the lots whose datumTot is before this time
+    #[arg(long, value_name = "DATETIME", value_parser = parse_datetime_arg)]
+    ended_before: Option<DateTime<Utc>>,
+
+    /// Drop the lots whose datumTot cannot be parsed instead of keeping them
+    #[arg(long)]
+    drop_unparseable_dates: bool,
+
+    /// Also combine the JSON files in every subdirectory of the input directory
+    #[arg(long)]
+    recursive: bool,
+
+    /// Report JSON files that cannot be read or parsed on stderr and carry on with the others,
+    /// failing only when no file could be combined
+    #[arg(long)]
+    skip_errors: bool,
+
+    /// Also write the combined items in this format; can be given more than once
+    #[arg(long, value_enum)]
+    format: Vec<Format>,
+
+    /// When fields of the CSV files are quoted
+    #[arg(long, value_enum, default_value_t = Quoting::Necessary)]
+    quote_style: Quoting,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    /// One JSON object per line, in combined_output.ndjson
+    Ndjson,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Quoting {
+    /// Only fields containing a delimiter, quote or line break
+    Necessary,
+    /// Every field
+    Always,
+    /// Every field that is not a number
+    NonNumeric,
+    /// No field, even if that produces invalid CSV
+    Never,
+}
+
+impl From<Quoting> for QuoteStyle {
+    fn from(quoting: Quoting) -> Self {
+        match quoting {
+            Quoting::Necessary => QuoteStyle::Necessary,
+            Quoting::Always => QuoteStyle::Always,
+            Quoting::NonNumeric => QuoteStyle::NonNumeric,
+            Quoting::Never => QuoteStyle::Never,
+        }
+    }
+}
+
+fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value {
+        "comma" => Ok(b','),
+        "semicolon" => Ok(b';'),
+        "tab" => Ok(b'\t'),
+        _ => match value.as_bytes() {
+            [byte] => Ok(*byte),
+            _ => Err(format!("expected comma, semicolon, tab or a single-byte character, got {:?}", value)),
+        },
+    }
+}
+
+fn parse_datetime_arg(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_datetime(value).ok_or_else(|| format!("expected a date or an RFC 3339 timestamp, got {:?}", value))
+}
+
+/// Whether the lot passes the --min-estimate/--max-estimate filter.
+fn in_estimate_range(args: &Args, item: &AuctionItem) -> bool {
+    item.low_estimate_value.is_some_and(|value| {
+        args.min_estimate.is_none_or(|min| value >= min) && args.max_estimate.is_none_or(|max| value <= max)
+    })
+}
+
+/// Whether the lot passes the --ended-after/--ended-before filter. Lots without a parseable
+/// datumTot are reported, whether or not a date filter is set, and kept unless
+/// --drop-unparseable-dates is set.
+fn in_date_range(args: &Args, item: &AuctionItem) -> bool {
+    match item.ended_at() {
+        Some(ended) => {
+            args.ended_after.is_none_or(|after| ended >= after)
+                && args.ended_before.is_none_or(|before| ended < before)
+        }
+        None => {
+            let action = if args.drop_unparseable_dates { "dropped" } else { "kept" };
+            println!("Item {}: unparseable datumTot {:?}, {}", item.Id, item.datumTot, action);
+            !args.drop_unparseable_dates
+        }
+    }
+}
 
-#[derive(Debug, Deserialize, Serialize)]
-struct AuctionItem {
-    Id: i64,
-    AuctioneerID: String,
-    Auction: String,
-    AuctSessionID: i64,
-    AuctSessionName: String,
-    GoedID: i64,
-    Lotnr: String,
-    Description: String,
-    LowEstimate: String,
-    HighEstimate: String,
-    Search: String,
-    ImageURL: String,
-    datumTot: String,
-    LowEstimateNum: i64,
+/// How many lots each filter of [`convert_and_filter`] dropped.
+#[derive(Default)]
+struct Dropped {
+    without_image: usize,
+    outside_estimates: usize,
+    out_of_range: usize,
+}
+
+/// Converts the estimates of `items` into the base currency when `rates` are given, then drops
+/// the lots the date, --images-only and estimate filters reject, so the estimate bounds are
+/// always compared with converted amounts.
+fn convert_and_filter(args: &Args, rates: Option<&RateTable>, items: &mut Vec<AuctionItem>, dropped: &mut Dropped) {
+    if let Some(rates) = rates {
+        for (id, reason) in normalize_currencies(items, rates) {
+            println!("Item {}: {}, left unconverted", id, reason);
+        }
+    }
+    // Runs even without a date filter, so every unparseable datumTot is reported
+    let before = items.len();
+    items.retain(|item| in_date_range(args, item));
+    dropped.out_of_range += before - items.len();
+    if args.images_only {
+        let before = items.len();
+        items.retain(AuctionItem::has_image);
+        dropped.without_image += before - items.len();
+    }
+    if args.min_estimate.is_some() || args.max_estimate.is_some() {
+        let before = items.len();
+        items.retain(|item| in_estimate_range(args, item));
+        dropped.outside_estimates += before - items.len();
+    }
+}
+
+fn csv_writer(args: &Args, path: &Path) -> Result<Writer<File>> {
+    WriterBuilder::new()
+        .delimiter(args.delimiter)
+        .quote_style(args.quote_style.into())
+        .from_path(path)
+        .with_context(|| format!("Failed to create file: {}", path.display()))
+}
+
+/// Names of the files written to the output directory. JSON inputs with one of these names are
+/// skipped, so the output of an earlier run in the same directory is never combined again.
+const COMBINED_JSON_FILE: &str = "combined_output.json";
+const COMBINED_CSV_FILE: &str = "output.csv";
+const COMBINED_NDJSON_FILE: &str = "combined_output.ndjson";
+const SUMMARY_FILE: &str = "summary.json";
+const OUTPUT_JSON_FILES: [&str; 2] = [COMBINED_JSON_FILE, SUMMARY_FILE];
+
+/// Every `*.json` file directly in `dir`, or anywhere beneath it when `recursive` is set, except
+/// the files an earlier run wrote there.
+fn json_files(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read directory: {}", dir.display()))? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+            } else if path.extension().and_then(|ext| ext.to_str()) == Some("json")
+                && !path.file_name().and_then(|name| name.to_str()).is_some_and(|name| OUTPUT_JSON_FILES.contains(&name))
+            {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Name of the file, kept in the input directory, where --incremental caches parsed inputs.
+/// It has no .json extension so it is never mistaken for an input.
+const STATE_FILE: &str = ".combine_state";
+
+/// The items parsed from one input file, with what identified its version when it was parsed.
+#[derive(Serialize, Deserialize)]
+struct CachedFile {
+    modified: SystemTime,
+    len: u64,
+    items: Vec<AuctionItem>,
+}
+
+/// The inputs seen by the last incremental run, by path.
+#[derive(Default, Serialize, Deserialize)]
+struct CombineState {
+    files: BTreeMap<PathBuf, CachedFile>,
+}
+
+/// Reads the state file, treating a missing one as a first run.
+fn load_state(path: &Path) -> Result<CombineState> {
+    match fs::read(path) {
+        Ok(content) => serde_json::from_slice(&content)
+            .with_context(|| format!("Failed to parse JSON from file: {}", path.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(CombineState::default()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read file: {}", path.display())),
+    }
+}
+
+/// Counts, per field, the items whose value is an empty string or a number that is zero or negative.
+fn quality_report(items: &[AuctionItem]) -> Vec<(&'static str, usize)> {
+    let blank = |f: fn(&AuctionItem) -> &str| items.iter().filter(|i| f(i).trim().is_empty()).count();
+    let non_positive = |f: fn(&AuctionItem) -> i64| items.iter().filter(|i| f(i) <= 0).count();
+    vec![
+        ("Id", non_positive(|i| i.Id)),
+        ("AuctioneerID", blank(|i| &i.AuctioneerID)),
+        ("Auction", blank(|i| &i.Auction)),
+        ("AuctSessionID", non_positive(|i| i.AuctSessionID)),
+        ("AuctSessionName", blank(|i| &i.AuctSessionName)),
+        ("GoedID", non_positive(|i| i.GoedID)),
+        ("Lotnr", blank(|i| &i.Lotnr)),
+        ("Description", blank(|i| &i.Description)),
+        ("LowEstimate", blank(|i| &i.LowEstimate)),
+        ("HighEstimate", blank(|i| &i.HighEstimate)),
+        ("Search", blank(|i| &i.Search)),
+        ("ImageURL", blank(|i| &i.ImageURL)),
+        ("datumTot", blank(|i| &i.datumTot)),
+        ("LowEstimateNum", non_positive(|i| i.LowEstimateNum)),
+    ]
+}
+
+fn print_quality_report(items: &[AuctionItem]) {
+    println!("Data quality of {} items", items.len());
+    println!("{:<16} {:>8}", "Field", "Missing");
+    for (field, missing) in quality_report(items) {
+        println!("{:<16} {:>8}", field, missing);
+    }
+}
+
+/// Whether `query` appears in the description or search text of `item`, ignoring case.
+fn item_matches(item: &AuctionItem, query: &str) -> bool {
+    let query = query.to_lowercase();
+    item.Description.to_lowercase().contains(&query) || item.Search.to_lowercase().contains(&query)
+}
+
+/// Reads queries from stdin and prints the matching lots until `:quit` or the end of input.
+fn search_repl(items: &[AuctionItem]) -> Result<()> {
+    println!("Loaded {} items. Type a keyword to search, :quit to stop.", items.len());
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let Some(line) = lines.next() else {
+            println!();
+            break;
+        };
+        let query = line?;
+        let query = query.trim();
+        if query == ":quit" {
+            break;
+        }
+        if query.is_empty() {
+            continue;
+        }
+        let matches: Vec<&AuctionItem> = items.iter().filter(|item| item_matches(item, query)).collect();
+        for item in &matches {
+            println!(
+                "{:>6}  {}  ({} - {})",
+                item.Lotnr.trim(),
+                item.Description,
+                item.LowEstimate,
+                item.HighEstimate
+            );
+        }
+        println!("{} matching lots", matches.len());
+    }
+    Ok(())
+}
+
+/// Escapes the characters that have a meaning in HTML text and attribute values.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders one card per item, with its thumbnail linking to the image, its description
+/// and its estimate range. The styles are inlined so the file can be opened on its own.
+///
+/// Only http(s) image URLs are linked, see [`AuctionItem::has_image`]; any other URL, such as a
+/// `javascript:` one, gets a placeholder instead.
+fn render_gallery(items: &[AuctionItem]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Auction lots</title>\n<style>\n\
+         body { font-family: sans-serif; margin: 1em; }\n\
+         .grid { display: grid; grid-template-columns: repeat(auto-fill, minmax(200px, 1fr)); gap: 1em; }\n\
+         .card { border: 1px solid #ccc; border-radius: 4px; padding: 0.5em; }\n\
+         .card img { width: 100%; height: 150px; object-fit: contain; }\n\
+         .card .no-image { height: 150px; display: flex; align-items: center; justify-content: center; background: #eee; color: #888; }\n\
+         .estimate { color: #555; }\n\
+         </style>\n</head>\n<body>\n<div class=\"grid\">\n",
+    );
+    for item in items {
+        let lot = escape_html(item.Lotnr.trim());
+        let image = if item.has_image() {
+            let url = escape_html(item.ImageURL.trim());
+            format!("<a href=\"{url}\"><img src=\"{url}\" alt=\"Lot {lot}\"></a>")
+        } else {
+            "<div class=\"no-image\">No image</div>".to_string()
+        };
+        html.push_str(&format!(
+            "<div class=\"card\">\n{image}\n\
+             <p>{lot}: {description}</p>\n<p class=\"estimate\">{low} - {high}</p>\n</div>\n",
+            description = escape_html(&item.Description),
+            low = escape_html(&item.LowEstimate),
+            high = escape_html(&item.HighEstimate),
+        ));
+    }
+    html.push_str("</div>\n</body>\n</html>\n");
+    html
+}
+
+/// Outcome of checking whether an image URL is reachable.
+enum LinkStatus {
+    Status(u16),
+    Timeout,
+    Error(String),
+}
+
+impl fmt::Display for LinkStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkStatus::Status(code) => write!(f, "{}", code),
+            LinkStatus::Timeout => write!(f, "timeout"),
+            LinkStatus::Error(e) => write!(f, "error: {}", e),
+        }
+    }
+}
+
+async fn head_status(client: &reqwest::Client, url: &str) -> LinkStatus {
+    match client.head(url).send().await {
+        Ok(response) => LinkStatus::Status(response.status().as_u16()),
+        Err(e) if e.is_timeout() => LinkStatus::Timeout,
+        Err(e) => {
+            // reqwest's own message only names the URL; the innermost cause says what failed
+            let mut cause: &dyn std::error::Error = &e;
+            while let Some(source) = cause.source() {
+                cause = source;
+            }
+            LinkStatus::Error(cause.to_string())
+        }
+    }
+}
+
+/// Sends a HEAD request to every non-empty `ImageURL`, at most `concurrency` at a time,
+/// without downloading any image. Results keep the order of `items`.
+fn check_images(items: &[AuctionItem], concurrency: usize, timeout: Duration) -> Result<Vec<(&AuctionItem, LinkStatus)>> {
+    let client = reqwest::Client::builder().timeout(timeout).build()?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    let checks = items
+        .iter()
+        .filter(|item| !item.ImageURL.trim().is_empty())
+        .map(|item| {
+            let client = &client;
+            async move { (item, head_status(client, &item.ImageURL).await) }
+        });
+    Ok(runtime.block_on(stream::iter(checks).buffered(concurrency.max(1)).collect()))
+}
+
+fn write_image_report(report: &[(&AuctionItem, LinkStatus)], mut writer: Writer<File>) -> Result<()> {
+    writer.write_record(["Id", "ImageURL", "Status"])?;
+    for (item, status) in report {
+        writer.write_record([item.Id.to_string(), item.ImageURL.clone(), status.to_string()])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn print_summary(summary: &Summary) {
+    println!("Items:    {}", summary.items);
+    println!("Auctions: {}", summary.auctions);
+    println!();
+    println!("{:<14} {:>6} {:>12} {:>12} {:>12} {:>12}", "Estimate", "Count", "Min", "Median", "Max", "Mean");
+    for (name, stats) in [("LowEstimate", &summary.low_estimate), ("HighEstimate", &summary.high_estimate)] {
+        println!(
+            "{:<14} {:>6} {:>12.2} {:>12.2} {:>12.2} {:>12.2}",
+            name, stats.count, stats.min, stats.median, stats.max, stats.mean
+        );
+    }
+}
+
+/// Writes the combined JSON, CSV and any extra formats one item at a time, so a run never needs
+/// to hold every item.
+///
+/// All files are written under a temporary name and renamed into place by [`CombinedOutput::finish`],
+/// so a half-written combined_output.json is never read back as an input.
+struct CombinedOutput {
+    json: BufWriter<File>,
+    csv: Writer<File>,
+    ndjson: Option<BufWriter<File>>,
+    items: usize,
+    renames: Vec<(PathBuf, PathBuf)>,
+}
+
+impl CombinedOutput {
+    fn create(args: &Args, output_dir: &Path) -> Result<Self> {
+        let json_path = output_dir.join(COMBINED_JSON_FILE);
+        let csv_path = output_dir.join(COMBINED_CSV_FILE);
+        let (json_tmp, csv_tmp) = (json_path.with_extension("json.tmp"), csv_path.with_extension("csv.tmp"));
+        let json = File::create(&json_tmp)
+            .with_context(|| format!("Failed to create file: {}", json_tmp.display()))?;
+        let mut csv = csv_writer(args, &csv_tmp)?;
+        csv.write_record(CSV_HEADER)?;
+        let mut renames = vec![(json_tmp, json_path), (csv_tmp, csv_path)];
+        let ndjson = if args.format.contains(&Format::Ndjson) {
+            let ndjson_path = output_dir.join(COMBINED_NDJSON_FILE);
+            let ndjson_tmp = ndjson_path.with_extension("ndjson.tmp");
+            let ndjson = File::create(&ndjson_tmp)
+                .with_context(|| format!("Failed to create file: {}", ndjson_tmp.display()))?;
+            renames.push((ndjson_tmp, ndjson_path));
+            Some(BufWriter::new(ndjson))
+        } else {
+            None
+        };
+        Ok(CombinedOutput { json: BufWriter::new(json), csv, ndjson, items: 0, renames })
+    }
+
+    /// Appends `item` to every file; the JSON array is laid out like `to_string_pretty` would.
+    fn write(&mut self, item: &AuctionItem) -> Result<()> {
+        self.json.write_all(if self.items == 0 { b"[\n" } else { b",\n" })?;
+        for (index, line) in serde_json::to_string_pretty(item)?.lines().enumerate() {
+            if index > 0 {
+                self.json.write_all(b"\n")?;
+            }
+            write!(self.json, "  {}", line)?;
+        }
+        self.csv.write_record(item.csv_record())?;
+        if let Some(ndjson) = self.ndjson.as_mut() {
+            serde_json::to_writer(&mut *ndjson, item)?;
+            ndjson.write_all(b"\n")?;
+        }
+        self.items += 1;
+        Ok(())
+    }
+
+    /// Closes the JSON array, flushes every file and moves them to their final names.
+    fn finish(mut self) -> Result<()> {
+        self.json.write_all(if self.items == 0 { b"[]" } else { b"\n]" })?;
+        self.json.flush()?;
+        self.csv.flush()?;
+        if let Some(ndjson) = self.ndjson.as_mut() {
+            ndjson.flush()?;
+        }
+        for (tmp, path) in &self.renames {
+            fs::rename(tmp, path).with_context(|| format!("Failed to write file: {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for CombinedOutput {
+    /// Removes the temporary files of a run that failed before [`CombinedOutput::finish`], leaving
+    /// any previous output in place.
+    fn drop(&mut self) {
+        for (tmp, _) in &self.renames {
+            let _ = fs::remove_file(tmp);
+        }
+    }
+}
+
+/// Reads and parses one JSON dump.
+fn read_items(path: &Path) -> Result<Vec<AuctionItem>> {
+    let content = fs::read(path)
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    parse_items(&content)
+        .with_context(|| format!("Failed to parse JSON from file: {}", path.display()))
 }
 
 fn main() -> Result<()> {
+    let args = Args::parse();
+
     // Specify the directory path where JSON files are located
-    let dir_path = "/Users/sramzzs4d/Projects-sramzz/rustTraining/auction_schipol/auction_json"; // Update this path
+    let input_dir = args.input_dir.as_path();
+    if !input_dir.is_dir() {
+        anyhow::bail!("Input directory does not exist: {}", input_dir.display());
+    }
+    let output_dir = args.output_dir.as_deref().unwrap_or(input_dir);
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create output directory: {}", output_dir.display()))?;
     
-    // Get all JSON files in the directory
+    let state_path = input_dir.join(STATE_FILE);
+    let mut previous_state = if args.incremental { load_state(&state_path)? } else { CombineState::default() };
+    // Files that are gone are simply not carried over from the previous state
+    let mut state = CombineState::default();
+    let mut reparsed = 0;
+
+    let rates: Option<RateTable> = match &args.rates {
+        Some(rates_path) => {
+            let content = fs::read(rates_path)
+                .with_context(|| format!("Failed to read file: {}", rates_path.display()))?;
+            Some(serde_json::from_slice(&content)
+                .with_context(|| format!("Failed to parse JSON from file: {}", rates_path.display()))?)
+        }
+        None => None,
+    };
+
+    // Items are only kept when a feature needs all of them at once; otherwise they are written
+    // out file by file as they are parsed
+    let keep_items = args.search
+        || args.quality_report
+        || args.check_images
+        || args.html.is_some()
+        || args.sqlite.is_some()
+        || args.session_totals
+        || args.summary;
+    let mut output = if args.search { None } else { Some(CombinedOutput::create(&args, output_dir)?) };
     let mut all_items = Vec::new();
+    let (mut seen, mut dropped) = (0, Dropped::default());
+    let (mut combined, mut skipped) = (0, 0);
     
-    for entry in fs::read_dir(dir_path)
-        .with_context(|| format!("Failed to read directory: {}", dir_path))? {
-        let entry = entry?;
-        let path = entry.path();
+    // Get all JSON files in the directory
+    for path in json_files(input_dir, args.recursive)? {
+        let metadata = fs::metadata(&path)?;
+        let (modified, len) = (metadata.modified()?, metadata.len());
+        let mut items = match previous_state.files.remove(&path) {
+            Some(cached) if cached.modified == modified && cached.len == len => cached.items,
+            _ => {
+                reparsed += 1;
+                match read_items(&path) {
+                    Ok(items) => items,
+                    Err(e) if args.skip_errors => {
+                        eprintln!("Skipping {}: {:#}", path.display(), e);
+                        skipped += 1;
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        };
+        combined += 1;
         
-        if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
-            // Read and parse JSON file
-            let content = fs::read_to_string(&path)
-                .with_context(|| format!("Failed to read file: {}", path.display()))?;
-            
-            let items: Vec<AuctionItem> = serde_json::from_str(&content)
-                .with_context(|| format!("Failed to parse JSON from file: {}", path.display()))?;
-            
-            all_items.extend(items);
+        if args.incremental {
+            state.files.insert(path, CachedFile { modified, len, items: items.clone() });
+        }
+
+        seen += items.len();
+        convert_and_filter(&args, rates.as_ref(), &mut items, &mut dropped);
+
+        for item in items {
+            if let Some(output) = output.as_mut() {
+                output.write(&item)?;
+            }
+            if keep_items {
+                all_items.push(item);
+            }
         }
     }
+
+    if args.incremental {
+        println!("Re-parsed {} of {} input files", reparsed, state.files.len());
+        fs::write(&state_path, serde_json::to_vec(&state)?)
+            .with_context(|| format!("Failed to write file: {}", state_path.display()))?;
+    }
     
-    // Save as JSON
-    let json_output = serde_json::to_string_pretty(&all_items)?;
-    let mut json_file = File::create(Path::new(dir_path).join("combined_output.json"))?;
-    json_file.write_all(json_output.as_bytes())?;
-    
-    // Save as CSV
-    let mut csv_writer = Writer::from_path(Path::new(dir_path).join("output.csv"))?;
-    
-    // Write CSV headers
-    csv_writer.write_record(&[
-        "Id",
-        "AuctioneerID",
-        "Auction",
-        "AuctSessionID",
-        "AuctSessionName",
-        "GoedID",
-        "Lotnr",
-        "Description",
-        "LowEstimate",
-        "HighEstimate",
-        "Search",
-        "ImageURL",
-        "datumTot",
-        "LowEstimateNum",
-    ])?;
-    
-    // Write data rows
-    for item in all_items {
-        csv_writer.write_record(&[
-            item.Id.to_string(),
-            item.AuctioneerID,
-            item.Auction,
-            item.AuctSessionID.to_string(),
-            item.AuctSessionName,
-            item.GoedID.to_string(),
-            item.Lotnr,
-            item.Description,
-            item.LowEstimate,
-            item.HighEstimate,
-            item.Search,
-            item.ImageURL,
-            item.datumTot,
-            item.LowEstimateNum.to_string(),
-        ])?;
+    if skipped > 0 {
+        eprintln!("Skipped {} of {} JSON files", skipped, combined + skipped);
+        if combined == 0 {
+            anyhow::bail!("None of the JSON files in {} could be combined", input_dir.display());
+        }
+    }
+
+    if args.images_only {
+        println!("Dropped {} of {} lots without a valid image URL", dropped.without_image, seen);
+    }
+    if args.min_estimate.is_some() || args.max_estimate.is_some() {
+        println!("Dropped {} of {} lots outside the estimate range", dropped.outside_estimates, seen);
+    }
+    if args.ended_after.is_some() || args.ended_before.is_some() || args.drop_unparseable_dates {
+        println!("Dropped {} of {} lots outside the date range", dropped.out_of_range, seen);
+    }
+
+    // Save the combined JSON and CSV
+    let Some(output) = output else {
+        return search_repl(&all_items);
+    };
+    output.finish()?;
+
+    if args.quality_report {
+        print_quality_report(&all_items);
+    }
+
+    if args.check_images {
+        let timeout = Duration::from_secs(args.check_timeout);
+        let report = check_images(&all_items, args.check_concurrency, timeout)?;
+        let reachable = report
+            .iter()
+            .filter(|(_, status)| matches!(status, LinkStatus::Status(code) if (200..300).contains(code)))
+            .count();
+        println!("{} of {} image URLs are reachable", reachable, report.len());
+        write_image_report(&report, csv_writer(&args, &output_dir.join("image_check.csv"))?)?;
+    }
+
+    if let Some(html_path) = &args.html {
+        fs::write(html_path, render_gallery(&all_items))
+            .with_context(|| format!("Failed to write file: {}", html_path.display()))?;
+    }
+
+    if let Some(sqlite_path) = &args.sqlite {
+        let mut connection = rusqlite::Connection::open(sqlite_path)
+            .with_context(|| format!("Failed to open database: {}", sqlite_path.display()))?;
+        write_sqlite(&mut connection, &all_items)
+            .with_context(|| format!("Failed to write database: {}", sqlite_path.display()))?;
+    }
+
+    if args.session_totals {
+        let mut writer = csv_writer(&args, &output_dir.join("session_totals.csv"))?;
+        writer.write_record(["AuctSessionName", "Lots", "TotalLowEstimate"])?;
+        for total in session_totals(&all_items) {
+            writer.write_record([total.session, total.lots.to_string(), total.total_low_estimate.to_string()])?;
+        }
+        writer.flush()?;
     }
     
-    csv_writer.flush()?;
+    if args.summary {
+        let summary = summarize(&all_items);
+        print_summary(&summary);
+        let summary_path = output_dir.join(SUMMARY_FILE);
+        fs::write(&summary_path, serde_json::to_string_pretty(&summary)?)
+            .with_context(|| format!("Failed to write file: {}", summary_path.display()))?;
+    }
     
     println!("Processing completed successfully!");
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An item as the site's JSON would have it, with the given image URL.
+    fn item(image_url: &str) -> AuctionItem {
+        serde_json::from_value(serde_json::json!({
+            "Id": 1, "AuctioneerID": "A", "Auction": "Spring", "AuctSessionID": 1,
+            "AuctSessionName": "Session", "GoedID": 1, "Lotnr": "7", "Description": "Vase",
+            "LowEstimate": "€ 100", "HighEstimate": "€ 200", "Search": "", "ImageURL": image_url,
+            "datumTot": "2025-01-27T16:00:28+00:00", "LowEstimateNum": 100,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn json_files_skips_every_json_file_this_binary_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["page.json", COMBINED_JSON_FILE, SUMMARY_FILE, COMBINED_CSV_FILE, STATE_FILE] {
+            fs::write(dir.path().join(name), "[]").unwrap();
+        }
+        assert_eq!(json_files(dir.path(), false).unwrap(), vec![dir.path().join("page.json")]);
+    }
+
+    #[test]
+    fn quality_report_counts_blank_and_non_positive_fields() {
+        let complete = item("https://example.com/1.jpg");
+        let mut no_image = item("");
+        no_image.LowEstimateNum = 0;
+        let mut blank = item("   ");
+        blank.Description = " ".to_string();
+        blank.LowEstimateNum = -5;
+        let report: BTreeMap<&str, usize> = quality_report(&[complete, no_image, blank]).into_iter().collect();
+        assert_eq!(report["ImageURL"], 2);
+        assert_eq!(report["Description"], 1);
+        assert_eq!(report["LowEstimateNum"], 2);
+        // Search is empty in every fixture item, the other fields are filled everywhere
+        assert_eq!(report["Search"], 3);
+        assert_eq!(report["Id"] + report["Auction"] + report["Lotnr"] + report["datumTot"], 0);
+    }
+
+    #[test]
+    fn delimiter_accepts_names_and_single_bytes() {
+        assert_eq!(parse_delimiter("comma"), Ok(b','));
+        assert_eq!(parse_delimiter("semicolon"), Ok(b';'));
+        assert_eq!(parse_delimiter("tab"), Ok(b'\t'));
+        assert_eq!(parse_delimiter("|"), Ok(b'|'));
+        assert!(parse_delimiter("::").is_err());
+        assert!(parse_delimiter("€").is_err());
+    }
+
+    #[test]
+    fn semicolon_csv_reads_back_with_the_same_delimiter() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(COMBINED_CSV_FILE);
+        let args = Args::try_parse_from(["auction_schipol", "--delimiter", "semicolon"]).unwrap();
+        let mut vase = item("https://example.com/7.jpg");
+        vase.Description = "Vase; blue, with lid".to_string();
+        let mut writer = csv_writer(&args, &path).unwrap();
+        auction_schipol::write_csv(&mut writer, std::slice::from_ref(&vase)).unwrap();
+        drop(writer);
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.lines().next().unwrap().starts_with("Id;AuctioneerID;"));
+        let mut reader = csv::ReaderBuilder::new().delimiter(b';').from_path(&path).unwrap();
+        let headers = reader.headers().unwrap().clone();
+        let records: Vec<csv::StringRecord> = reader.records().collect::<Result<_, _>>().unwrap();
+        assert_eq!(headers.iter().collect::<Vec<_>>(), CSV_HEADER);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].iter().collect::<Vec<_>>(), vase.csv_record());
+    }
+
+    #[test]
+    fn item_matches_description_or_search_ignoring_case() {
+        let mut vase = item("");
+        vase.Search = "Delft blauw".to_string();
+        assert!(item_matches(&vase, "vase"));
+        assert!(item_matches(&vase, "VAS"));
+        assert!(item_matches(&vase, "delft"));
+        assert!(!item_matches(&vase, "teapot"));
+    }
+
+    #[test]
+    fn gallery_renders_one_escaped_card_per_item() {
+        let mut tag = item("https://example.com/8.jpg");
+        tag.Description = "<script>alert(\"x\")</script> & co".to_string();
+        let html = render_gallery(&[item("https://example.com/7.jpg"), tag, item("")]);
+        assert_eq!(html.matches("<div class=\"card\">").count(), 3);
+        assert!(html.contains("&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt; &amp; co"));
+        assert!(!html.contains("<script>"));
+    }
+
+    /// Serves HEAD requests on a local port: `/ok` answers 200, `/missing` 404 and `/slow` never
+    /// answers in time.
+    fn mock_image_server() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                std::thread::spawn(move || {
+                    let mut request_line = String::new();
+                    io::BufReader::new(&stream).read_line(&mut request_line).unwrap();
+                    let status = match request_line.split_whitespace().nth(1) {
+                        Some("/ok") => "200 OK",
+                        Some("/missing") => "404 Not Found",
+                        _ => {
+                            std::thread::sleep(Duration::from_secs(5));
+                            return;
+                        }
+                    };
+                    let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                    let _ = stream.write_all(response.as_bytes());
+                });
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    #[test]
+    fn check_images_classifies_each_url() {
+        let server = mock_image_server();
+        let items = [
+            item(&format!("{server}/ok")),
+            item(""),
+            item(&format!("{server}/missing")),
+            item(&format!("{server}/slow")),
+        ];
+        let report = check_images(&items, 4, Duration::from_millis(500)).unwrap();
+        // The item without an image URL is not checked
+        assert_eq!(report.len(), 3);
+        assert!(matches!(report[0].1, LinkStatus::Status(200)));
+        assert!(matches!(report[1].1, LinkStatus::Status(404)));
+        assert!(matches!(report[2].1, LinkStatus::Timeout));
+        assert!(report[0].0.ImageURL.ends_with("/ok") && report[2].0.ImageURL.ends_with("/slow"));
+    }
+
+    #[test]
+    fn foreign_estimates_are_converted_before_filtering_and_summarizing() {
+        let rates: RateTable = [("€".to_string(), 1.0), ("$".to_string(), 0.5)].into_iter().collect();
+        let lot = |id: i64, low: &str, high: &str| {
+            let mut item = item("");
+            item.Id = id;
+            item.LowEstimate = low.to_string();
+            item.HighEstimate = high.to_string();
+            item.parse_estimates();
+            item
+        };
+        // In dollars the first lot is above the range, in euros it lies within it
+        let mut items = vec![lot(1, "$ 400", "$ 600"), lot(2, "€ 400", "€ 600"), lot(3, "€ 100", "€ 150")];
+        let args = Args::try_parse_from(["auction_schipol", "--min-estimate", "150", "--max-estimate", "300"]).unwrap();
+        let mut dropped = Dropped::default();
+        convert_and_filter(&args, Some(&rates), &mut items, &mut dropped);
+
+        assert_eq!(items.iter().map(|item| item.Id).collect::<Vec<_>>(), [1]);
+        assert_eq!(dropped.outside_estimates, 2);
+        assert_eq!(items[0].LowEstimateNum, 200);
+        let summary = summarize(&items);
+        assert_eq!((summary.low_estimate.max, summary.high_estimate.max), (200.0, 300.0));
+    }
+
+    #[test]
+    fn gallery_links_only_http_images() {
+        let html = render_gallery(&[item("https://example.com/7.jpg"), item("javascript:alert(1)")]);
+        assert_eq!(html.matches("<img src=\"https://example.com/7.jpg\"").count(), 1);
+        assert!(!html.contains("javascript:"));
+        assert_eq!(html.matches("No image").count(), 1);
+    }
+}
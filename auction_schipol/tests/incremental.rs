@@ -0,0 +1,47 @@
+//! Runs the combiner twice over a copy of the bundled dumps to check what --incremental reuses.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Runs the binary with `--incremental` over `input_dir`, writing into `output_dir`, and returns
+/// its stdout.
+fn combine(input_dir: &Path, output_dir: &Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_auction_schipol"))
+        .arg("--input-dir")
+        .arg(input_dir)
+        .arg("--output-dir")
+        .arg(output_dir)
+        .arg("--incremental")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn second_run_without_changes_reparses_nothing() {
+    let input = tempfile::tempdir().unwrap();
+    let pages = ["first_page_Auction.json", "second_page_Auction.json", "third_page_Auction.json"];
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("auction_json");
+    for page in pages {
+        fs::copy(fixtures.join(page), input.path().join(page)).unwrap();
+    }
+    let (first, second) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+
+    assert!(combine(input.path(), first.path()).contains("Re-parsed 3 of 3 input files"));
+    assert!(combine(input.path(), second.path()).contains("Re-parsed 0 of 3 input files"));
+    for file in ["combined_output.json", "output.csv"] {
+        assert_eq!(
+            fs::read(first.path().join(file)).unwrap(),
+            fs::read(second.path().join(file)).unwrap(),
+            "{} differs",
+            file
+        );
+    }
+
+    // A deleted input is dropped from the state and only the changed one is parsed again
+    fs::remove_file(input.path().join(pages[2])).unwrap();
+    fs::write(input.path().join(pages[1]), "[]").unwrap();
+    assert!(combine(input.path(), second.path()).contains("Re-parsed 1 of 2 input files"));
+}
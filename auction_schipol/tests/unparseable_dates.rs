@@ -0,0 +1,32 @@
+//! Runs the combiner over a dump with a datumTot that cannot be parsed.
+
+use std::fs;
+use std::process::Command;
+
+/// A dump of two lots, the second with a datumTot that is not a date.
+const DUMP: &str = r#"[
+    {"Id": 1, "AuctioneerID": "A", "Auction": "Spring", "AuctSessionID": 1, "AuctSessionName": "Session",
+     "GoedID": 1, "Lotnr": "1", "Description": "Vase", "LowEstimate": "€ 100", "HighEstimate": "€ 200",
+     "Search": "", "ImageURL": "", "datumTot": "2025-01-27T16:00:28+00:00", "LowEstimateNum": 100},
+    {"Id": 2, "AuctioneerID": "A", "Auction": "Spring", "AuctSessionID": 1, "AuctSessionName": "Session",
+     "GoedID": 2, "Lotnr": "2", "Description": "Lamp", "LowEstimate": "€ 50", "HighEstimate": "€ 80",
+     "Search": "", "ImageURL": "", "datumTot": "soon", "LowEstimateNum": 50}
+]"#;
+
+#[test]
+fn unparseable_dates_are_reported_without_a_date_filter() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("page.json"), DUMP).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_auction_schipol"))
+        .arg("--input-dir")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Item 2: unparseable datumTot \"soon\", kept"), "{}", stdout);
+    assert!(!stdout.contains("Item 1:"));
+    // Without --drop-unparseable-dates the lot is still written
+    let csv = fs::read_to_string(dir.path().join("output.csv")).unwrap();
+    assert_eq!(csv.lines().count(), 3);
+}